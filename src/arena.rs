@@ -21,6 +21,13 @@ impl<T: Clone> Arena<T> {
         }
     }
 
+    pub fn get_mut(&mut self, index: usize) -> &mut T {
+        match self.current {
+            Heap::A => &mut self.a[index],
+            Heap::B => &mut self.b[index],
+        }
+    }
+
     pub fn len(&self) -> usize {
         match self.current {
             Heap::A => self.a.len(),
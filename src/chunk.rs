@@ -1,12 +1,18 @@
-use std::fmt;
+use std::{
+    fmt,
+    io::{self, Write},
+};
 
 use crate::arena::*;
 use crate::value::*;
 
+const OPERAND_COLUMN: usize = 24;
+
 #[derive(Debug, Clone)]
 pub struct Chunk {
     code: Vec<Op>,
-    lines: Vec<usize>,
+    // Run-length encoded as (line, number of consecutive ops on that line).
+    lines: Vec<(usize, usize)>,
     constants: Vec<Value>,
 }
 
@@ -21,10 +27,9 @@ impl Chunk {
 
     pub fn write(&mut self, byte: Op, line: usize) {
         self.code.push(byte);
-        if line >= self.lines.len() {
-            self.lines.push(1);
-        } else {
-            self.lines[line - 1] += 1;
+        match self.lines.last_mut() {
+            Some((last_line, count)) if *last_line == line => *count += 1,
+            _ => self.lines.push((line, 1)),
         }
     }
 
@@ -33,22 +38,27 @@ impl Chunk {
         self.constants.len() - 1
     }
 
-    pub fn disassemble(&self, name: &str, objects: &Arena<Obj>) {
-        println!("== {name} ==");
-        print!("Constants: ");
+    pub fn disassemble(
+        &self,
+        name: &str,
+        objects: &Arena<Obj>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        writeln!(out, "== {name} ==")?;
+        write!(out, "Constants: ")?;
         for (constant_index, constant) in self.constants.iter().enumerate() {
             match constant {
-                Value::Obj(index) => print!("{constant_index}:[ {} ] ", objects.get(*index)),
-                _ => print!("{constant_index}:[ {constant} ] "),
+                Value::Obj(index) => write!(out, "{constant_index}:[ {} ] ", objects.get(*index))?,
+                _ => write!(out, "{constant_index}:[ {constant} ] ")?,
             }
         }
 
-        println!();
+        writeln!(out)?;
         for (instruction_number, byte) in self.code.iter().enumerate() {
-            self.disassemble_instruction(instruction_number, byte, objects);
+            self.disassemble_instruction(instruction_number, byte, objects, out)?;
         }
 
-        println!("==\\ {name} ==")
+        writeln!(out, "==\\ {name} ==")
     }
 
     pub fn read_op(&self, index: usize) -> &Op {
@@ -57,41 +67,53 @@ impl Chunk {
             .expect("Operation read error - instruction index is out-of-bounds")
     }
 
+    pub fn constants(&self) -> &[Value] {
+        &self.constants
+    }
+
     pub fn read_constant(&self, index: usize) -> &Value {
         self.constants
             .get(index)
             .expect("Constant read error - index for constant is out-of-bounds")
     }
 
-    pub fn disassemble_instruction(&self, offset: usize, instruction: &Op, objects: &Arena<Obj>) {
-        print!("{:04} ", offset);
+    fn disassemble_instruction(
+        &self,
+        offset: usize,
+        instruction: &Op,
+        objects: &Arena<Obj>,
+        out: &mut dyn Write,
+    ) -> io::Result<()> {
+        write!(out, "{:04} ", offset)?;
         let current_line = self.get_line(offset);
         if offset > 0 && current_line == self.get_line(offset - 1) {
-            print!("    | ");
+            write!(out, "    | ")?;
         } else {
-            print!("{number:>width$} ", number = current_line, width = 5);
+            write!(out, "{number:>width$} ", number = current_line, width = 5)?;
         }
 
         match instruction {
             Op::Constant(index) => {
                 let value = &self.constants[*index];
                 match value {
-                    Value::Obj(index) => println!("{instruction} '{}'", objects.get(*index)),
-                    _ => println!("{instruction} '{value}'"),
+                    Value::Obj(index) => writeln!(out, "{instruction} '{}'", objects.get(*index)),
+                    _ => writeln!(out, "{instruction} '{value}'"),
                 }
             }
-            _ => println!("{instruction}"),
-        };
+            _ => writeln!(out, "{instruction}"),
+        }
     }
 
     pub fn get_line(&self, offset: usize) -> usize {
-        let mut line_counter = self.lines[0];
-        let mut current_index = 1;
-        while line_counter < offset && current_index < self.lines.len() {
-            line_counter += self.lines[current_index];
-            current_index += 1;
+        let mut op_counter = 0;
+        for (line, count) in &self.lines {
+            op_counter += count;
+            if offset < op_counter {
+                return *line;
+            }
         }
-        current_index
+
+        panic!("Line read error - instruction index {offset} is out-of-bounds")
     }
 
     pub fn code_len(&self) -> usize {
@@ -116,6 +138,9 @@ pub enum Op {
     True,
     False,
     Pop,
+    Dup,
+    DupPair,
+    Rotate(usize),
     DefineGlobal(usize),
     GetGlobal(usize),
     SetGlobal(usize),
@@ -128,70 +153,151 @@ pub enum Op {
     Subtract,
     Multiply,
     Divide,
+    FloorDivide,
+    Modulo,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    BitNot,
+    ShiftLeft,
+    ShiftRight,
     Not,
     Negate,
     Print,
     JumpIfFalse(usize),
+    JumpIfNotNil(usize),
     Jump(usize),
     Loop(usize),
+    Call(usize),
+    Closure(usize),
+    GetUpvalue(usize),
+    SetUpvalue(usize),
+    CloseUpvalue,
+    PushHandler(usize),
+    PopHandler,
+    Throw,
+    Import(usize),
+    Export(usize),
+    GetProperty(usize),
+    Iterate,
+    IterNext,
+    Range,
+    RangeInclusive,
+    In,
+    Yield,
+    BuildList(usize),
+    BuildMap(usize),
+    BuildString(usize),
+    GetIndex,
+    SetIndex,
     Return,
 }
 
+impl Op {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Constant(_) => "CONSTANT",
+            Self::Nil => "NIL",
+            Self::True => "TRUE",
+            Self::False => "FALSE",
+            Self::Pop => "POP",
+            Self::Dup => "DUP",
+            Self::DupPair => "DUP_PAIR",
+            Self::Rotate(_) => "ROTATE",
+            Self::DefineGlobal(_) => "DEFINE_GLOBAL",
+            Self::GetGlobal(_) => "GET_GLOBAL",
+            Self::SetGlobal(_) => "SET_GLOBAL",
+            Self::GetLocal(_) => "GET_LOCAL",
+            Self::SetLocal(_) => "SET_LOCAL",
+            Self::Equal => "EQUAL",
+            Self::Greater => "GREATER",
+            Self::Less => "LESS",
+            Self::Add => "ADD",
+            Self::Subtract => "SUBTRACT",
+            Self::Multiply => "MULTIPLY",
+            Self::Divide => "DIVIDE",
+            Self::FloorDivide => "FLOOR_DIVIDE",
+            Self::Modulo => "MODULO",
+            Self::Power => "POWER",
+            Self::BitAnd => "BIT_AND",
+            Self::BitOr => "BIT_OR",
+            Self::BitXor => "BIT_XOR",
+            Self::BitNot => "BIT_NOT",
+            Self::ShiftLeft => "SHIFT_LEFT",
+            Self::ShiftRight => "SHIFT_RIGHT",
+            Self::Not => "NOT",
+            Self::Negate => "NEGATE",
+            Self::Print => "PRINT",
+            Self::JumpIfFalse(_) => "JUMP_IF_FALSE",
+            Self::JumpIfNotNil(_) => "JUMP_IF_NOT_NIL",
+            Self::Jump(_) => "JUMP",
+            Self::Loop(_) => "LOOP",
+            Self::Call(_) => "CALL",
+            Self::Closure(_) => "CLOSURE",
+            Self::GetUpvalue(_) => "GET_UPVALUE",
+            Self::SetUpvalue(_) => "SET_UPVALUE",
+            Self::CloseUpvalue => "CLOSE_UPVALUE",
+            Self::PushHandler(_) => "PUSH_HANDLER",
+            Self::PopHandler => "POP_HANDLER",
+            Self::Throw => "THROW",
+            Self::Import(_) => "IMPORT",
+            Self::Export(_) => "EXPORT",
+            Self::GetProperty(_) => "GET_PROPERTY",
+            Self::Iterate => "ITERATE",
+            Self::IterNext => "ITER_NEXT",
+            Self::Range => "RANGE",
+            Self::RangeInclusive => "RANGE_INCLUSIVE",
+            Self::In => "IN",
+            Self::Yield => "YIELD",
+            Self::BuildList(_) => "BUILD_LIST",
+            Self::BuildMap(_) => "BUILD_MAP",
+            Self::BuildString(_) => "BUILD_STRING",
+            Self::GetIndex => "GET_INDEX",
+            Self::SetIndex => "SET_INDEX",
+            Self::Return => "RETURN",
+        }
+    }
+
+    pub fn operand(&self) -> Option<usize> {
+        match self {
+            Self::Constant(operand)
+            | Self::Rotate(operand)
+            | Self::DefineGlobal(operand)
+            | Self::GetGlobal(operand)
+            | Self::SetGlobal(operand)
+            | Self::GetLocal(operand)
+            | Self::SetLocal(operand)
+            | Self::JumpIfFalse(operand)
+            | Self::JumpIfNotNil(operand)
+            | Self::Jump(operand)
+            | Self::Loop(operand)
+            | Self::Call(operand)
+            | Self::Closure(operand)
+            | Self::GetUpvalue(operand)
+            | Self::SetUpvalue(operand)
+            | Self::PushHandler(operand)
+            | Self::Import(operand)
+            | Self::Export(operand)
+            | Self::GetProperty(operand)
+            | Self::BuildList(operand)
+            | Self::BuildMap(operand)
+            | Self::BuildString(operand) => Some(*operand),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for Op {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            Self::Constant(index) => {
-                write!(f, "CONSTANT {number:>width$}", number = index, width = 16)
-            }
-            Self::Nil => write!(f, "NIL"),
-            Self::True => write!(f, "TRUE"),
-            Self::False => write!(f, "FALSE"),
-            Self::Pop => write!(f, "POP"),
-            Self::DefineGlobal(index) => {
-                write!(
-                    f,
-                    "DEFINE_GLOBAL {number:>width$}",
-                    number = index,
-                    width = 11
-                )
-            }
-            Self::GetGlobal(index) => {
-                write!(f, "GET_GLOBAL {number:>width$}", number = index, width = 14)
-            }
-            Self::SetGlobal(index) => {
-                write!(f, "SET_GLOBAL {number:>width$}", number = index, width = 14)
-            }
-            Self::GetLocal(index) => {
-                write!(f, "GET_LOCAL {number:>width$}", number = index, width = 15)
-            }
-            Self::SetLocal(index) => {
-                write!(f, "SET_LOCAL {number:>width$}", number = index, width = 15)
-            }
-            Self::Equal => write!(f, "EQUAL"),
-            Self::Greater => write!(f, "GREATER"),
-            Self::Less => write!(f, "LESS"),
-            Self::Add => write!(f, "ADD"),
-            Self::Subtract => write!(f, "SUBTRACT"),
-            Self::Multiply => write!(f, "MULTIPLY"),
-            Self::Divide => write!(f, "DIVIDE"),
-            Self::Not => write!(f, "NOT"),
-            Self::Negate => write!(f, "NEGATE"),
-            Self::Print => write!(f, "PRINT"),
-            Self::JumpIfFalse(index) => {
-                write!(
-                    f,
-                    "JUMP_IF_FALSE {number:>width$}",
-                    number = index,
-                    width = 11
-                )
-            }
-            Self::Jump(index) => {
-                write!(f, "JUMP {number:>width$}", number = index, width = 20)
-            }
-            Self::Loop(index) => {
-                write!(f, "LOOP {number:>width$}", number = index, width = 20)
-            }
-            Self::Return => write!(f, "RETURN"),
+        let name = self.name();
+        match self.operand() {
+            Some(operand) => write!(
+                f,
+                "{name} {operand:>width$}",
+                width = OPERAND_COLUMN - name.len()
+            ),
+            None => write!(f, "{name}"),
         }
     }
 }
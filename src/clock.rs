@@ -0,0 +1,96 @@
+use std::{
+    cell::Cell,
+    rc::Rc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Where scripts get the time from. Hand a `VmConfig` a different one to
+/// control time in tests.
+pub trait Clock {
+    /// Seconds since the Unix epoch.
+    fn now(&self) -> f64;
+
+    /// Seconds since an arbitrary fixed point. Never goes backwards.
+    fn monotonic(&self) -> f64;
+
+    fn sleep(&self, duration: Duration);
+}
+
+/// The real time, with the monotonic clock starting at zero.
+#[derive(Debug, Clone)]
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> f64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |elapsed| elapsed.as_secs_f64())
+    }
+
+    fn monotonic(&self) -> f64 {
+        self.start.elapsed().as_secs_f64()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// A clock that only moves when told to or when a script sleeps. Clones
+/// share the same time, so one can be kept to drive a VM's copy.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Rc<Cell<f64>>,
+    monotonic: Rc<Cell<f64>>,
+}
+
+impl ManualClock {
+    pub fn new(now: f64) -> Self {
+        Self {
+            now: Rc::new(Cell::new(now)),
+            monotonic: Rc::new(Cell::new(0.0)),
+        }
+    }
+
+    /// Changes the wall clock without touching the monotonic one, as when
+    /// the system time is adjusted.
+    pub fn set(&self, now: f64) {
+        self.now.set(now);
+    }
+
+    pub fn advance(&self, seconds: f64) {
+        self.now.set(self.now.get() + seconds);
+        self.monotonic.set(self.monotonic.get() + seconds);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> f64 {
+        self.now.get()
+    }
+
+    fn monotonic(&self) -> f64 {
+        self.monotonic.get()
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration.as_secs_f64());
+    }
+}
@@ -1,33 +1,37 @@
-use std::{env, mem};
+use std::mem;
 
 use crate::{arena::*, chunk::*, scanner::*, token::*, value::*};
 
 const UNINITIALIZED_SCOPE: isize = -1;
 const GLOBAL_SCOPE: usize = 0;
+const MAX_ARGUMENTS: usize = 255;
+const LAMBDA_NAME: &str = "lambda";
 
 #[derive(Debug)]
 pub struct Compiler<'a> {
     scanner: Scanner,
     parser: Parser,
-    locals: Vec<Local>,
-    scope_depth: usize,
-    pub chunk: Chunk,
+    states: Vec<FunctionState>,
+    module: usize,
     pub objects: &'a mut Arena<Obj>,
 }
 
 impl<'a> Compiler<'a> {
-    pub fn new(source: String, objects: &'a mut Arena<Obj>) -> Self {
+    pub fn new(source: String, objects: &'a mut Arena<Obj>, module: usize) -> Self {
         Self {
             scanner: Scanner::new(source),
             parser: Parser::new(),
-            locals: Vec::new(),
-            scope_depth: 0,
-            chunk: Chunk::new(),
-            objects: objects,
+            states: vec![FunctionState::new(
+                FunctionKind::Script,
+                String::new(),
+                module,
+            )],
+            module,
+            objects,
         }
     }
 
-    pub fn compile(mut self) -> Result<Compiler<'a>, ()> {
+    pub fn compile(mut self) -> Result<Function, Vec<String>> {
         self.parser.reset();
 
         self.advance();
@@ -35,16 +39,13 @@ impl<'a> Compiler<'a> {
             self.declaration();
         }
 
-        self.end();
-        if env::var("DEBUG_PRINT_CODE").is_ok_and(|var| var == "1") {
-            self.chunk.disassemble("code", self.objects);
-        }
+        let function = self.end();
 
         if self.parser.had_error {
-            return Err(());
+            return Err(self.parser.errors);
         }
 
-        Ok(self)
+        Ok(function)
     }
 
     fn advance(&mut self) {
@@ -55,7 +56,7 @@ impl<'a> Compiler<'a> {
                 break;
             }
 
-            self.parser.error("");
+            self.parser.error_at_current();
         }
     }
 
@@ -77,55 +78,99 @@ impl<'a> Compiler<'a> {
         true
     }
 
-    fn end(&mut self) {
-        self.emit_byte(Op::Return);
+    fn state(&self) -> &FunctionState {
+        self.states.last().expect("Compiler has no function state.")
+    }
+
+    fn state_mut(&mut self) -> &mut FunctionState {
+        self.states
+            .last_mut()
+            .expect("Compiler has no function state.")
+    }
+
+    fn chunk(&mut self) -> &mut Chunk {
+        &mut self.state_mut().function.chunk
+    }
+
+    fn end(&mut self) -> Function {
+        self.emit_return();
+        self.states
+            .pop()
+            .expect("Compiler has no function state.")
+            .function
     }
 
     fn begin_scope(&mut self) {
-        self.scope_depth += 1;
+        self.state_mut().scope_depth += 1;
     }
 
     fn end_scope(&mut self) {
-        self.scope_depth -= 1;
-        let mut locals = Vec::new();
-        for local in &self.locals {
-            if local.depth <= self.scope_depth as isize {
-                locals.push(local.clone());
-            } else {
-                self.chunk.write(Op::Pop, self.parser.previous.line)
+        self.state_mut().scope_depth -= 1;
+        let scope_depth = self.state().scope_depth as isize;
+        while let Some(local) = self.state().locals.last() {
+            if local.depth <= scope_depth {
+                break;
             }
-        }
 
-        self.locals = self.clear_scope();
-    }
-
-    fn clear_scope(&self) -> Vec<Local> {
-        self.locals
-            .iter()
-            .take_while(|local| local.depth < self.scope_depth as isize)
-            .map(|local| local.clone())
-            .collect()
+            let op = if local.is_captured {
+                Op::CloseUpvalue
+            } else {
+                Op::Pop
+            };
+            self.emit_byte(op);
+            self.state_mut().locals.pop();
+        }
     }
 
     fn binary(&mut self) {
         let op_type = self.parser.previous.typ;
         let rule = op_type.get_rule();
-        self.parse_precedence(rule.precedence.next());
+        let operand_precedence = match op_type {
+            TokenType::StarStar => rule.precedence,
+            _ => rule.precedence.next(),
+        };
+        self.parse_precedence(operand_precedence);
         match op_type {
             TokenType::Plus => self.emit_byte(Op::Add),
             TokenType::Minus => self.emit_byte(Op::Subtract),
             TokenType::Star => self.emit_byte(Op::Multiply),
             TokenType::Slash => self.emit_byte(Op::Divide),
+            TokenType::TildeSlash => self.emit_byte(Op::FloorDivide),
+            TokenType::Percent => self.emit_byte(Op::Modulo),
+            TokenType::StarStar => self.emit_byte(Op::Power),
+            TokenType::Ampersand => self.emit_byte(Op::BitAnd),
+            TokenType::Pipe => self.emit_byte(Op::BitOr),
+            TokenType::Caret => self.emit_byte(Op::BitXor),
+            TokenType::LessLess => self.emit_byte(Op::ShiftLeft),
+            TokenType::GreaterGreater => self.emit_byte(Op::ShiftRight),
             TokenType::BangEqual => self.emit_bytes(Op::Equal, Op::Not),
             TokenType::EqualEqual => self.emit_byte(Op::Equal),
             TokenType::Greater => self.emit_byte(Op::Greater),
             TokenType::GreaterEqual => self.emit_bytes(Op::Less, Op::Not),
             TokenType::Less => self.emit_byte(Op::Less),
             TokenType::LessEqual => self.emit_bytes(Op::Greater, Op::Not),
+            TokenType::In => self.emit_byte(Op::In),
             _ => panic!("Unreachable code: unknown binary operation {op_type}"),
         }
     }
 
+    fn range(&mut self) {
+        let op = match self.parser.previous.typ {
+            TokenType::DotDotEqual => Op::RangeInclusive,
+            _ => Op::Range,
+        };
+        self.parse_precedence(Precedence::Range.next());
+
+        if self.parser.check(TokenType::Identifier) && self.parser.current.lexeme == "step" {
+            self.advance();
+            self.parse_precedence(Precedence::Range.next());
+        } else {
+            self.emit_byte(Op::Nil);
+        }
+
+        self.emit_byte(op);
+    }
+
     fn literal(&mut self) {
         match self.parser.previous.typ {
             TokenType::False => self.emit_byte(Op::False),
@@ -139,10 +184,107 @@ impl<'a> Compiler<'a> {
     }
 
     fn grouping(&mut self) {
+        if self.is_arrow_function() {
+            self.arrow_function();
+            return;
+        }
+
         self.expression();
         self.consume(TokenType::RightParen, "Expect ')' after expression");
     }
 
+    fn is_arrow_function(&mut self) -> bool {
+        let checkpoint = self.scanner.checkpoint();
+        let mut token_type = self.parser.current.typ;
+        let mut expect_parameter = true;
+        let is_arrow = loop {
+            match token_type {
+                TokenType::RightParen => {
+                    break self.scanner.scan_token().typ == TokenType::Arrow;
+                }
+                TokenType::Identifier if expect_parameter => expect_parameter = false,
+                TokenType::Comma if !expect_parameter => expect_parameter = true,
+                _ => break false,
+            }
+            token_type = self.scanner.scan_token().typ;
+        };
+
+        self.scanner.restore(checkpoint);
+        is_arrow
+    }
+
+    fn is_lambda(&mut self) -> bool {
+        let checkpoint = self.scanner.checkpoint();
+        let is_lambda = self.scanner.scan_token().typ == TokenType::LeftParen;
+        self.scanner.restore(checkpoint);
+        is_lambda
+    }
+
+    fn arrow_function(&mut self) {
+        self.states.push(FunctionState::new(
+            FunctionKind::Function,
+            String::from(LAMBDA_NAME),
+            self.module,
+        ));
+        self.begin_scope();
+        self.parameters();
+        self.consume(TokenType::Arrow, "Expect '=>' after parameters.");
+
+        if self.match_advance(TokenType::LeftBrace) {
+            self.block();
+        } else {
+            self.expression();
+            self.emit_byte(Op::Return);
+        }
+
+        self.emit_closure();
+    }
+
+    fn lambda(&mut self) {
+        self.function(FunctionKind::Function, String::from(LAMBDA_NAME));
+    }
+
+    fn function(&mut self, kind: FunctionKind, name: String) {
+        self.states
+            .push(FunctionState::new(kind, name, self.module));
+        self.begin_scope();
+
+        self.consume(TokenType::LeftParen, "Expect '(' after function name.");
+        self.parameters();
+        self.consume(TokenType::LeftBrace, "Expect '{' before function body.");
+        self.block();
+
+        self.emit_closure();
+    }
+
+    fn parameters(&mut self) {
+        if !self.parser.check(TokenType::RightParen) {
+            loop {
+                if self.state().function.arity == MAX_ARGUMENTS {
+                    self.parser.error("Can't have more than 255 parameters.");
+                }
+                self.state_mut().function.arity += 1;
+
+                let constant = self.parse_variable("Expect parameter name.");
+                self.define_variable(constant);
+
+                if !self.match_advance(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::RightParen, "Expect ')' after parameters.");
+    }
+
+    fn emit_closure(&mut self) {
+        let function = self.end();
+        self.objects.push(Obj::Function(function));
+        let function = Value::Obj(self.objects.len() - 1);
+        let index = self.chunk().add_constant(function);
+        self.emit_byte(Op::Closure(index));
+    }
+
     fn expression(&mut self) {
         self.parse_precedence(Precedence::Assignment);
     }
@@ -171,6 +313,64 @@ impl<'a> Compiler<'a> {
         self.define_variable(global);
     }
 
+    fn fun_declaration(&mut self) {
+        let global = self.parse_variable("Expect function name.");
+        let name = self.parser.previous.lexeme.clone();
+        self.mark_initialized();
+        self.function(FunctionKind::Function, name);
+        self.define_variable(global);
+    }
+
+    fn import_statement(&mut self) {
+        if self.match_advance(TokenType::Str) {
+            self.module_path();
+            self.emit_byte(Op::Pop);
+            self.consume(TokenType::SemiColon, "Expect ';' after import.");
+            return;
+        }
+
+        let variable = self.parse_variable("Expect module name or path after 'import'.");
+        if self.parser.check(TokenType::Identifier) && self.parser.current.lexeme == "from" {
+            self.advance();
+        } else {
+            self.parser.error("Expect 'from' after module name.");
+        }
+
+        self.consume(TokenType::Str, "Expect module path string.");
+        self.module_path();
+        self.consume(TokenType::SemiColon, "Expect ';' after import.");
+        self.define_variable(variable);
+    }
+
+    fn module_path(&mut self) {
+        self.objects
+            .push(Obj::Str(self.parser.previous.lexeme.clone()));
+        let path = Value::Obj(self.objects.len() - 1);
+        let index = self.chunk().add_constant(path);
+        self.emit_byte(Op::Import(index));
+    }
+
+    fn export_declaration(&mut self) {
+        if self.state().kind != FunctionKind::Script || !self.is_global_scope() {
+            self.parser.error("Can only export top-level declarations.");
+            return;
+        }
+
+        let declaration = if self.match_advance(TokenType::Var) {
+            Self::var_declaration
+        } else if self.match_advance(TokenType::Fun) {
+            Self::fun_declaration
+        } else {
+            self.parser.error("Expect 'var' or 'fun' after 'export'.");
+            return;
+        };
+
+        let name = self.parser.current.lexeme.clone();
+        declaration(self);
+        let name = self.identifier_constant(name);
+        self.emit_byte(Op::Export(name));
+    }
+
     fn expression_statement(&mut self) {
         self.expression();
         self.consume(TokenType::SemiColon, "Expect ';' after expression.");
@@ -202,8 +402,73 @@ impl<'a> Compiler<'a> {
         self.emit_byte(Op::Print);
     }
 
+    fn return_statement(&mut self) {
+        if self.state().kind == FunctionKind::Script {
+            self.parser.error("Can't return from top-level code.");
+        }
+
+        if self.match_advance(TokenType::SemiColon) {
+            self.emit_return();
+        } else {
+            self.expression();
+            self.consume(TokenType::SemiColon, "Expect ';' after return value.");
+            self.emit_byte(Op::Return);
+        }
+    }
+
+    fn throw_statement(&mut self) {
+        self.expression();
+        self.consume(TokenType::SemiColon, "Expect ';' after thrown value.");
+        self.emit_byte(Op::Throw);
+    }
+
+    fn try_statement(&mut self) {
+        let handler = self.emit_jump(Op::PushHandler(0));
+        self.consume(TokenType::LeftBrace, "Expect '{' after 'try'.");
+        self.begin_scope();
+        self.block();
+        self.end_scope();
+        self.emit_byte(Op::PopHandler);
+        let end_jump = self.emit_jump(Op::Jump(0));
+
+        // The VM unwinds to here with the error on the stack.
+        self.patch_jump(handler);
+        if !self.match_advance(TokenType::Catch) {
+            self.parser.error("Expect 'catch' after try block.");
+            return;
+        }
+
+        self.consume(TokenType::LeftParen, "Expect '(' after 'catch'.");
+        self.begin_scope();
+        let variable = self.parse_variable("Expect error variable name.");
+        self.define_variable(variable);
+        self.consume(TokenType::RightParen, "Expect ')' after error variable.");
+        self.consume(TokenType::LeftBrace, "Expect '{' before catch body.");
+        self.begin_scope();
+        self.block();
+        self.end_scope();
+        self.end_scope();
+
+        self.patch_jump(end_jump);
+    }
+
+    fn yield_statement(&mut self) {
+        if self.state().kind == FunctionKind::Script {
+            self.parser.error("Can't yield from top-level code.");
+        }
+        self.state_mut().function.is_generator = true;
+
+        if self.match_advance(TokenType::SemiColon) {
+            self.emit_byte(Op::Nil);
+        } else {
+            self.expression();
+            self.consume(TokenType::SemiColon, "Expect ';' after yielded value.");
+        }
+        self.emit_byte(Op::Yield);
+    }
+
     fn while_statement(&mut self) {
-        let loop_start = self.chunk.code_len();
+        let loop_start = self.chunk().code_len();
         self.consume(TokenType::LeftParen, "Expect '(' after 'while'.");
         self.expression();
         self.consume(TokenType::RightParen, "Expect ')' after condition.");
@@ -220,6 +485,12 @@ impl<'a> Compiler<'a> {
     fn for_statement(&mut self) {
         self.begin_scope();
         self.consume(TokenType::LeftParen, "Expect '(' after 'for'.");
+        if self.is_for_in() {
+            self.for_in_statement();
+            self.end_scope();
+            return;
+        }
+
         if self.match_advance(TokenType::SemiColon) {
             // no initializer
         } else if self.match_advance(TokenType::Var) {
@@ -228,7 +499,7 @@ impl<'a> Compiler<'a> {
             self.expression_statement();
         }
 
-        let mut loop_start = self.chunk.code_len();
+        let mut loop_start = self.chunk().code_len();
         let mut exit_jump = -1;
         if !self.match_advance(TokenType::SemiColon) {
             self.expression();
@@ -239,7 +510,7 @@ impl<'a> Compiler<'a> {
 
         if !self.match_advance(TokenType::RightParen) {
             let body_jump = self.emit_jump(Op::Jump(0));
-            let increment_start = self.chunk.code_len();
+            let increment_start = self.chunk().code_len();
             self.expression();
             self.emit_byte(Op::Pop);
             self.consume(TokenType::RightParen, "Expect ')' after for clauses.");
@@ -259,6 +530,50 @@ impl<'a> Compiler<'a> {
         self.end_scope();
     }
 
+    fn is_for_in(&mut self) -> bool {
+        let checkpoint = self.scanner.checkpoint();
+        let mut token_type = self.parser.current.typ;
+        if token_type == TokenType::Var {
+            token_type = self.scanner.scan_token().typ;
+        }
+
+        let is_for_in =
+            token_type == TokenType::Identifier && self.scanner.scan_token().typ == TokenType::In;
+        self.scanner.restore(checkpoint);
+        is_for_in
+    }
+
+    fn for_in_statement(&mut self) {
+        self.match_advance(TokenType::Var);
+        self.consume(TokenType::Identifier, "Expect loop variable name.");
+        let name = self.parser.previous.lexeme.clone();
+        self.consume(TokenType::In, "Expect 'in' after loop variable.");
+        self.expression();
+        self.consume(TokenType::RightParen, "Expect ')' after for clauses.");
+
+        // The iterator lives in a local no script can name.
+        self.emit_byte(Op::Iterate);
+        self.add_local(String::from(" iterator"));
+        self.mark_initialized();
+        let iterator = self.state().locals.len() - 1;
+
+        let loop_start = self.chunk().code_len();
+        self.emit_bytes(Op::GetLocal(iterator), Op::IterNext);
+        let exit_jump = self.emit_jump(Op::JumpIfFalse(0));
+        self.emit_byte(Op::Pop);
+
+        // A fresh variable per pass, so closures capture their own.
+        self.begin_scope();
+        self.add_local(name);
+        self.mark_initialized();
+        self.statement();
+        self.end_scope();
+        self.emit_loop(loop_start);
+
+        self.patch_jump(exit_jump);
+        self.emit_bytes(Op::Pop, Op::Pop);
+    }
+
     fn synchronize(&mut self) {
         self.parser.panic_mode = false;
         while self.parser.current.typ != TokenType::Eof {
@@ -274,14 +589,26 @@ impl<'a> Compiler<'a> {
                 | TokenType::If
                 | TokenType::While
                 | TokenType::Print
-                | TokenType::Return => return,
+                | TokenType::Return
+                | TokenType::Try
+                | TokenType::Throw
+                | TokenType::Import
+                | TokenType::Export
+                | TokenType::Yield => return,
                 _ => self.advance(),
             }
         }
     }
 
     fn declaration(&mut self) {
-        if self.match_advance(TokenType::Var) {
+        if self.parser.check(TokenType::Fun) && !self.is_lambda() {
+            self.advance();
+            self.fun_declaration();
+        } else if self.match_advance(TokenType::Export) {
+            self.export_declaration();
+        } else if self.match_advance(TokenType::Import) {
+            self.import_statement();
+        } else if self.match_advance(TokenType::Var) {
             self.var_declaration();
         } else {
             self.statement();
@@ -305,15 +632,24 @@ impl<'a> Compiler<'a> {
             self.end_scope();
         } else if self.match_advance(TokenType::If) {
             self.if_statement();
+        } else if self.match_advance(TokenType::Return) {
+            self.return_statement();
+        } else if self.match_advance(TokenType::Yield) {
+            self.yield_statement();
+        } else if self.match_advance(TokenType::Try) {
+            self.try_statement();
+        } else if self.match_advance(TokenType::Throw) {
+            self.throw_statement();
         } else {
             self.expression_statement();
         }
     }
 
     fn number(&mut self) {
-        let lexeme = &self.parser.previous.lexeme;
-        let number = Value::Number(lexeme.parse().unwrap());
-        self.make_constant(number);
+        match parse_number(&self.parser.previous.lexeme) {
+            Some(number) => self.make_constant(Value::Number(number)),
+            None => self.parser.error("Invalid number literal."),
+        }
     }
 
     fn string(&mut self) {
@@ -324,34 +660,175 @@ impl<'a> Compiler<'a> {
         self.make_constant(Value::Obj(self.objects.len() - 1));
     }
 
-    fn named_variable(&mut self, name: String, can_assign: bool) {
-        let arg = self.resolve_local(&name);
-        let (get_op, set_op) = match arg {
-            Some(a) => (Op::GetLocal(a), Op::SetLocal(a)),
-            None => {
-                let arg = self.identifier_constant(name);
-                (Op::GetGlobal(arg), Op::SetGlobal(arg))
+    fn interpolation(&mut self) {
+        let mut part_count = 0;
+        loop {
+            self.string();
+            self.expression();
+            part_count += 2;
+
+            if !self.match_advance(TokenType::Interpolation) {
+                break;
             }
+        }
+
+        self.consume(TokenType::Str, "Expect end of string interpolation.");
+        self.string();
+        self.emit_byte(Op::BuildString(part_count + 1));
+    }
+
+    fn named_variable(&mut self, name: String, can_assign: bool) {
+        let current = self.states.len() - 1;
+        let (get_op, set_op) = if let Some(arg) = self.resolve_local(current, &name) {
+            (Op::GetLocal(arg), Op::SetLocal(arg))
+        } else if let Some(arg) = self.resolve_upvalue(current, &name) {
+            (Op::GetUpvalue(arg), Op::SetUpvalue(arg))
+        } else {
+            let arg = self.identifier_constant(name);
+            (Op::GetGlobal(arg), Op::SetGlobal(arg))
         };
 
         if can_assign && self.match_advance(TokenType::Equal) {
             self.expression();
             self.emit_byte(set_op);
+        } else if let Some(op) = self.compound_assignment(can_assign) {
+            self.emit_byte(get_op);
+            self.expression();
+            self.emit_bytes(op, set_op);
+        } else if let Some(op) = self.postfix_increment() {
+            self.emit_bytes(get_op, Op::Dup);
+            self.make_constant(Value::Number(1.0));
+            self.emit_bytes(op, set_op);
+            self.emit_byte(Op::Pop);
         } else {
             self.emit_byte(get_op);
         }
     }
 
+    fn compound_assignment(&mut self, can_assign: bool) -> Option<Op> {
+        if !can_assign {
+            return None;
+        }
+
+        let op = match self.parser.current.typ {
+            TokenType::PlusEqual => Op::Add,
+            TokenType::MinusEqual => Op::Subtract,
+            TokenType::StarEqual => Op::Multiply,
+            TokenType::SlashEqual => Op::Divide,
+            TokenType::PercentEqual => Op::Modulo,
+            _ => return None,
+        };
+
+        self.advance();
+        Some(op)
+    }
+
+    fn postfix_increment(&mut self) -> Option<Op> {
+        if self.match_advance(TokenType::PlusPlus) {
+            Some(Op::Add)
+        } else if self.match_advance(TokenType::MinusMinus) {
+            Some(Op::Subtract)
+        } else {
+            None
+        }
+    }
+
     fn variable(&mut self, can_assign: bool) {
         self.named_variable(self.parser.previous.lexeme.clone(), can_assign)
     }
 
+    fn call(&mut self) {
+        let arg_count = self.argument_list();
+        self.emit_byte(Op::Call(arg_count));
+    }
+
+    fn argument_list(&mut self) -> usize {
+        let mut arg_count = 0;
+        if !self.parser.check(TokenType::RightParen) {
+            loop {
+                self.expression();
+                if arg_count == MAX_ARGUMENTS {
+                    self.parser.error("Can't have more than 255 arguments.");
+                }
+                arg_count += 1;
+
+                if !self.match_advance(TokenType::Comma) {
+                    break;
+                }
+            }
+        }
+
+        self.consume(TokenType::RightParen, "Expect ')' after arguments.");
+        arg_count
+    }
+
+    fn dot(&mut self) {
+        self.consume(TokenType::Identifier, "Expect property name after '.'.");
+        let name = self.identifier_constant(self.parser.previous.lexeme.clone());
+        self.emit_byte(Op::GetProperty(name));
+    }
+
+    fn list(&mut self) {
+        let mut element_count = 0;
+        while !self.parser.check(TokenType::RightBracket) {
+            self.expression();
+            element_count += 1;
+
+            if !self.match_advance(TokenType::Comma) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::RightBracket, "Expect ']' after list elements.");
+        self.emit_byte(Op::BuildList(element_count));
+    }
+
+    fn map(&mut self) {
+        let mut entry_count = 0;
+        while !self.parser.check(TokenType::RightBrace) {
+            self.expression();
+            self.consume(TokenType::Colon, "Expect ':' after map key.");
+            self.expression();
+            entry_count += 1;
+
+            if !self.match_advance(TokenType::Comma) {
+                break;
+            }
+        }
+
+        self.consume(TokenType::RightBrace, "Expect '}' after map entries.");
+        self.emit_byte(Op::BuildMap(entry_count));
+    }
+
+    fn index(&mut self, can_assign: bool) {
+        self.expression();
+        self.consume(TokenType::RightBracket, "Expect ']' after index.");
+
+        if can_assign && self.match_advance(TokenType::Equal) {
+            self.expression();
+            self.emit_byte(Op::SetIndex);
+        } else if let Some(op) = self.compound_assignment(can_assign) {
+            self.emit_bytes(Op::DupPair, Op::GetIndex);
+            self.expression();
+            self.emit_bytes(op, Op::SetIndex);
+        } else if let Some(op) = self.postfix_increment() {
+            self.emit_bytes(Op::DupPair, Op::GetIndex);
+            self.emit_bytes(Op::Dup, Op::Rotate(3));
+            self.make_constant(Value::Number(1.0));
+            self.emit_bytes(op, Op::SetIndex);
+            self.emit_byte(Op::Pop);
+        } else {
+            self.emit_byte(Op::GetIndex);
+        }
+    }
+
     fn unary(&mut self) {
         let op_type = self.parser.previous.typ;
         self.parse_precedence(Precedence::Unary);
         match op_type {
             TokenType::Bang => self.emit_byte(Op::Not),
             TokenType::Minus => self.emit_byte(Op::Negate),
+            TokenType::Tilde => self.emit_byte(Op::BitNot),
             _ => panic!("Unreachable code: unknown unary operation {op_type}"),
         }
     }
@@ -372,11 +849,13 @@ impl<'a> Compiler<'a> {
             self.advance();
             match self.parser.previous.typ.get_rule().infix {
                 Method::NonAssign(infix_rule) => infix_rule(self),
+                Method::Assign(infix_rule) => infix_rule(self, can_assign),
                 _ => panic!("Unreachable code: expected infix rule"),
             };
         }
 
-        if can_assign && self.match_advance(TokenType::Equal) {
+        if can_assign && self.parser.current.typ.is_assignment() {
+            self.advance();
             self.parser.error("Invalid assignment target.");
         }
     }
@@ -384,11 +863,12 @@ impl<'a> Compiler<'a> {
     fn identifier_constant(&mut self, name: String) -> usize {
         let ident = Obj::Ident(name);
         self.objects.push(ident);
-        self.chunk.add_constant(Value::Obj(self.objects.len() - 1))
+        let value = Value::Obj(self.objects.len() - 1);
+        self.chunk().add_constant(value)
     }
 
-    fn resolve_local(&mut self, name: &String) -> Option<usize> {
-        for (index, local) in self.locals.iter().enumerate().rev() {
+    fn resolve_local(&mut self, state: usize, name: &String) -> Option<usize> {
+        for (index, local) in self.states[state].locals.iter().enumerate().rev() {
             if &local.name == name {
                 if local.depth == UNINITIALIZED_SCOPE {
                     self.parser
@@ -401,9 +881,37 @@ impl<'a> Compiler<'a> {
         None
     }
 
+    fn resolve_upvalue(&mut self, state: usize, name: &String) -> Option<usize> {
+        if state == 0 {
+            return None;
+        }
+
+        if let Some(local) = self.resolve_local(state - 1, name) {
+            self.states[state - 1].locals[local].is_captured = true;
+            return Some(self.add_upvalue(state, local, true));
+        }
+
+        if let Some(upvalue) = self.resolve_upvalue(state - 1, name) {
+            return Some(self.add_upvalue(state, upvalue, false));
+        }
+
+        None
+    }
+
+    fn add_upvalue(&mut self, state: usize, index: usize, is_local: bool) -> usize {
+        let upvalue = UpvalueRef { is_local, index };
+        let upvalues = &mut self.states[state].function.upvalues;
+        if let Some(existing) = upvalues.iter().position(|u| *u == upvalue) {
+            return existing;
+        }
+
+        upvalues.push(upvalue);
+        upvalues.len() - 1
+    }
+
     fn add_local(&mut self, name: String) {
         let local = Local::new(name, UNINITIALIZED_SCOPE);
-        self.locals.push(local);
+        self.state_mut().locals.push(local);
     }
 
     fn declare_variable(&mut self) {
@@ -412,8 +920,9 @@ impl<'a> Compiler<'a> {
         }
 
         let name = self.parser.previous.lexeme.clone();
-        for local in self.locals.iter().rev() {
-            if local.depth != UNINITIALIZED_SCOPE && local.depth < self.scope_depth as isize {
+        let scope_depth = self.state().scope_depth as isize;
+        for local in self.states[self.states.len() - 1].locals.iter().rev() {
+            if local.depth != UNINITIALIZED_SCOPE && local.depth < scope_depth {
                 break;
             }
 
@@ -438,9 +947,15 @@ impl<'a> Compiler<'a> {
     }
 
     fn mark_initialized(&mut self) {
-        let index = self.locals.len() - 1;
-        let local = &mut self.locals[index];
-        local.depth = self.scope_depth as isize;
+        if self.is_global_scope() {
+            return;
+        }
+
+        let state = self.state_mut();
+        let depth = state.scope_depth as isize;
+        if let Some(local) = state.locals.last_mut() {
+            local.depth = depth;
+        }
     }
 
     fn define_variable(&mut self, global: usize) {
@@ -468,24 +983,54 @@ impl<'a> Compiler<'a> {
         self.patch_jump(end_jump);
     }
 
+    fn conditional(&mut self) {
+        let else_jump = self.emit_jump(Op::JumpIfFalse(0));
+        self.emit_byte(Op::Pop);
+        self.parse_precedence(Precedence::Conditional);
+        self.consume(
+            TokenType::Colon,
+            "Expect ':' after then branch of conditional expression.",
+        );
+
+        let end_jump = self.emit_jump(Op::Jump(0));
+        self.patch_jump(else_jump);
+        self.emit_byte(Op::Pop);
+        self.parse_precedence(Precedence::Conditional);
+        self.patch_jump(end_jump);
+    }
+
+    fn nil_coalesce(&mut self) {
+        let end_jump = self.emit_jump(Op::JumpIfNotNil(0));
+        self.emit_byte(Op::Pop);
+        self.parse_precedence(Precedence::Or);
+        self.patch_jump(end_jump);
+    }
+
     fn patch_jump(&mut self, offset: usize) {
-        let jump = self.chunk.code_len();
-        let op = match self.chunk.get_op(offset) {
+        let jump = self.chunk().code_len();
+        let op = match self.chunk().get_op(offset) {
             Op::JumpIfFalse(_) => Op::JumpIfFalse(jump),
+            Op::JumpIfNotNil(_) => Op::JumpIfNotNil(jump),
             Op::Jump(_) => Op::Jump(jump),
+            Op::PushHandler(_) => Op::PushHandler(jump),
             op => panic!("Op {op} at {offset} is not a valid jump op."),
         };
 
-        *self.chunk.get_op_mut(offset) = op;
+        *self.chunk().get_op_mut(offset) = op;
     }
 
     fn emit_jump(&mut self, byte: Op) -> usize {
-        self.chunk.write(byte, self.parser.previous.line);
-        self.chunk.code_len() - 1
+        self.emit_byte(byte);
+        self.chunk().code_len() - 1
     }
 
     fn emit_byte(&mut self, byte: Op) {
-        self.chunk.write(byte, self.parser.previous.line);
+        let line = self.parser.previous.line;
+        self.chunk().write(byte, line);
+    }
+
+    fn emit_return(&mut self) {
+        self.emit_bytes(Op::Nil, Op::Return);
     }
 
     fn emit_bytes(&mut self, first: Op, second: Op) {
@@ -498,21 +1043,37 @@ impl<'a> Compiler<'a> {
     }
 
     fn make_constant(&mut self, value: Value) {
-        let index = self.chunk.add_constant(value);
+        let index = self.chunk().add_constant(value);
         self.emit_byte(Op::Constant(index));
     }
 
     fn is_global_scope(&self) -> bool {
-        self.scope_depth == GLOBAL_SCOPE
+        self.state().scope_depth == GLOBAL_SCOPE
     }
 }
 
+fn parse_number(lexeme: &str) -> Option<f64> {
+    let digits: String = lexeme.chars().filter(|c| *c != '_').collect();
+    let radix = match digits.get(..2) {
+        Some("0x" | "0X") => 16,
+        Some("0o" | "0O") => 8,
+        Some("0b" | "0B") => 2,
+        _ => return digits.parse().ok(),
+    };
+
+    digits[2..].chars().try_fold(0.0, |number, c| {
+        c.to_digit(radix)
+            .map(|digit| number * radix as f64 + digit as f64)
+    })
+}
+
 #[derive(Debug, Clone)]
 struct Parser {
     previous: Token,
     current: Token,
     had_error: bool,
     panic_mode: bool,
+    errors: Vec<String>,
 }
 
 impl Parser {
@@ -522,12 +1083,14 @@ impl Parser {
             current: Token::empty(),
             had_error: false,
             panic_mode: false,
+            errors: Vec::new(),
         }
     }
 
     fn reset(&mut self) {
         self.had_error = false;
         self.panic_mode = false;
+        self.errors.clear();
     }
 
     fn check(&self, typ: TokenType) -> bool {
@@ -535,16 +1098,29 @@ impl Parser {
     }
 
     fn error(&mut self, message: &str) {
-        let m = if message.is_empty() {
-            &self.current.message
-        } else {
-            message
+        let token = self.previous.clone();
+        self.error_at(&token, message);
+    }
+
+    fn error_at_current(&mut self) {
+        let token = self.current.clone();
+        self.error_at(&token, &token.message);
+    }
+
+    fn error_at(&mut self, token: &Token, message: &str) {
+        if self.panic_mode {
+            return;
+        }
+
+        let location = match token.typ {
+            TokenType::Error => String::new(),
+            _ => format!(" at '{}'", token.lexeme),
         };
 
-        eprintln!(
-            "[line {} col {} len {}] Error at '{}': {}",
-            self.previous.line, self.previous.start, self.previous.length, self.previous.lexeme, m,
-        );
+        self.errors.push(format!(
+            "[line {} col {} len {}] Error{location}: {message}",
+            token.line, token.start, token.length,
+        ));
 
         self.had_error = true;
         self.panic_mode = true;
@@ -555,26 +1131,64 @@ impl Parser {
 struct Local {
     name: String,
     depth: isize,
+    is_captured: bool,
 }
 
 impl Local {
     fn new(name: String, depth: isize) -> Local {
-        Local { name, depth }
+        Local {
+            name,
+            depth,
+            is_captured: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionKind {
+    Script,
+    Function,
+}
+
+#[derive(Debug)]
+struct FunctionState {
+    function: Function,
+    kind: FunctionKind,
+    locals: Vec<Local>,
+    scope_depth: usize,
+}
+
+impl FunctionState {
+    fn new(kind: FunctionKind, name: String, module: usize) -> Self {
+        Self {
+            function: Function::new(name, module),
+            kind,
+            locals: vec![Local::new(String::new(), GLOBAL_SCOPE as isize)],
+            scope_depth: GLOBAL_SCOPE,
+        }
     }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 enum Precedence {
     None,
-    Assignment, // =
-    Or,         // or
-    And,        // and
-    Equality,   // == !=
-    Comparison, // < > <= >=
-    Term,       // + -
-    Factor,     // * /
-    Unary,      // ! -
-    Call,       // . ()
+    Assignment,  // =
+    Conditional, // ?:
+    Coalesce,    // ??
+    Or,          // or
+    And,         // and
+    Equality,    // == !=
+    Comparison,  // < > <= >= in
+    Range,       // .. ..=
+    BitOr,       // |
+    BitXor,      // ^
+    BitAnd,      // &
+    Shift,       // << >>
+    Term,        // + -
+    Factor,      // * / ~/ %
+    Unary,       // ! - ~
+    Exponent,    // **
+    Call,        // . () []
     Primary,
 }
 
@@ -582,23 +1196,34 @@ impl Precedence {
     pub fn next(&self) -> Self {
         match self {
             Self::None => Self::Assignment,
-            Self::Assignment => Self::Or,
+            Self::Assignment => Self::Conditional,
+            Self::Conditional => Self::Coalesce,
+            Self::Coalesce => Self::Or,
             Self::Or => Self::And,
             Self::And => Self::Equality,
             Self::Equality => Self::Comparison,
-            Self::Comparison => Self::Term,
+            Self::Comparison => Self::Range,
+            Self::Range => Self::BitOr,
+            Self::BitOr => Self::BitXor,
+            Self::BitXor => Self::BitAnd,
+            Self::BitAnd => Self::Shift,
+            Self::Shift => Self::Term,
             Self::Term => Self::Factor,
             Self::Factor => Self::Unary,
-            Self::Unary => Self::Call,
+            Self::Unary => Self::Exponent,
+            Self::Exponent => Self::Call,
             Self::Call => Self::Primary,
             Self::Primary => Self::Primary,
         }
     }
 }
 
+type NonAssignRule = Box<dyn Fn(&mut Compiler)>;
+type AssignRule = Box<dyn Fn(&mut Compiler, bool)>;
+
 enum Method {
-    NonAssign(Box<dyn Fn(&mut Compiler)>),
-    Assign(Box<dyn Fn(&mut Compiler, bool)>),
+    NonAssign(NonAssignRule),
+    Assign(AssignRule),
     None,
 }
 
@@ -617,8 +1242,8 @@ impl GetRule for TokenType {
         match self {
             Self::LeftParen => ParseRule {
                 prefix: Method::NonAssign(Box::new(|compiler| compiler.grouping())),
-                infix: Method::None,
-                precedence: Precedence::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.call())),
+                precedence: Precedence::Call,
             },
             Self::RightParen => ParseRule {
                 prefix: Method::None,
@@ -626,7 +1251,7 @@ impl GetRule for TokenType {
                 precedence: Precedence::None,
             },
             Self::LeftBrace => ParseRule {
-                prefix: Method::None,
+                prefix: Method::NonAssign(Box::new(|compiler| compiler.map())),
                 infix: Method::None,
                 precedence: Precedence::None,
             },
@@ -635,16 +1260,26 @@ impl GetRule for TokenType {
                 infix: Method::None,
                 precedence: Precedence::None,
             },
-            Self::Comma => ParseRule {
+            Self::LeftBracket => ParseRule {
+                prefix: Method::NonAssign(Box::new(|compiler| compiler.list())),
+                infix: Method::Assign(Box::new(|compiler, can_assign| compiler.index(can_assign))),
+                precedence: Precedence::Call,
+            },
+            Self::RightBracket => ParseRule {
                 prefix: Method::None,
                 infix: Method::None,
                 precedence: Precedence::None,
             },
-            Self::Dot => ParseRule {
+            Self::Comma => ParseRule {
                 prefix: Method::None,
                 infix: Method::None,
                 precedence: Precedence::None,
             },
+            Self::Dot => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.dot())),
+                precedence: Precedence::Call,
+            },
             Self::Minus => ParseRule {
                 prefix: Method::NonAssign(Box::new(|compiler| compiler.unary())),
                 infix: Method::NonAssign(Box::new(|compiler| compiler.binary())),
@@ -675,6 +1310,111 @@ impl GetRule for TokenType {
                 infix: Method::NonAssign(Box::new(|compiler| compiler.binary())),
                 precedence: Precedence::Factor,
             },
+            Self::Percent => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.binary())),
+                precedence: Precedence::Factor,
+            },
+            Self::TildeSlash => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.binary())),
+                precedence: Precedence::Factor,
+            },
+            Self::StarStar => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.binary())),
+                precedence: Precedence::Exponent,
+            },
+            Self::Ampersand => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.binary())),
+                precedence: Precedence::BitAnd,
+            },
+            Self::Pipe => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.binary())),
+                precedence: Precedence::BitOr,
+            },
+            Self::Caret => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.binary())),
+                precedence: Precedence::BitXor,
+            },
+            Self::LessLess => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.binary())),
+                precedence: Precedence::Shift,
+            },
+            Self::GreaterGreater => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.binary())),
+                precedence: Precedence::Shift,
+            },
+            Self::Tilde => ParseRule {
+                prefix: Method::NonAssign(Box::new(|compiler| compiler.unary())),
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::PlusEqual => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::MinusEqual => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::StarEqual => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::SlashEqual => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::PercentEqual => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::PlusPlus => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::MinusMinus => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::Question => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.conditional())),
+                precedence: Precedence::Conditional,
+            },
+            Self::DotDot => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.range())),
+                precedence: Precedence::Range,
+            },
+            Self::DotDotEqual => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.range())),
+                precedence: Precedence::Range,
+            },
+            Self::Arrow => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::QuestionQuestion => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.nil_coalesce())),
+                precedence: Precedence::Coalesce,
+            },
             Self::Bang => ParseRule {
                 prefix: Method::NonAssign(Box::new(|compiler| compiler.unary())),
                 infix: Method::None,
@@ -727,6 +1467,11 @@ impl GetRule for TokenType {
                 infix: Method::None,
                 precedence: Precedence::None,
             },
+            Self::Interpolation => ParseRule {
+                prefix: Method::NonAssign(Box::new(|compiler| compiler.interpolation())),
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
             Self::Number => ParseRule {
                 prefix: Method::NonAssign(Box::new(|compiler| compiler.number())),
                 infix: Method::None,
@@ -758,7 +1503,7 @@ impl GetRule for TokenType {
                 precedence: Precedence::None,
             },
             Self::Fun => ParseRule {
-                prefix: Method::None,
+                prefix: Method::NonAssign(Box::new(|compiler| compiler.lambda())),
                 infix: Method::None,
                 precedence: Precedence::None,
             },
@@ -832,6 +1577,41 @@ impl GetRule for TokenType {
                 infix: Method::None,
                 precedence: Precedence::None,
             },
+            Self::Try => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::Catch => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::Throw => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::Import => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::Export => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
+            Self::In => ParseRule {
+                prefix: Method::None,
+                infix: Method::NonAssign(Box::new(|compiler| compiler.binary())),
+                precedence: Precedence::Comparison,
+            },
+            Self::Yield => ParseRule {
+                prefix: Method::None,
+                infix: Method::None,
+                precedence: Precedence::None,
+            },
             Self::Error => ParseRule {
                 prefix: Method::None,
                 infix: Method::None,
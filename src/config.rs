@@ -0,0 +1,130 @@
+use std::{
+    cell::RefCell,
+    io::{self, BufRead, Write},
+    rc::Rc,
+};
+
+use crate::clock::{Clock, SystemClock};
+
+pub struct VmConfig {
+    pub(crate) trace_execution: bool,
+    pub(crate) print_code: bool,
+    pub(crate) trace_output: Box<dyn Write>,
+    pub(crate) output: Box<dyn Write>,
+    pub(crate) diagnostics: Box<dyn Write>,
+    pub(crate) io: bool,
+    // Read from stdin when not set.
+    pub(crate) input: Option<Box<dyn BufRead>>,
+    // Taken from the clock when not set.
+    pub(crate) seed: Option<u64>,
+    pub(crate) clock: Box<dyn Clock>,
+}
+
+impl VmConfig {
+    pub fn new() -> Self {
+        Self {
+            trace_execution: false,
+            print_code: false,
+            trace_output: Box::new(io::stdout()),
+            output: Box::new(io::stdout()),
+            diagnostics: Box::new(io::stderr()),
+            io: true,
+            input: None,
+            seed: None,
+            clock: Box::new(SystemClock::new()),
+        }
+    }
+
+    /// Emit one JSON line per executed instruction to the trace output.
+    pub fn trace_execution(mut self, enabled: bool) -> Self {
+        self.trace_execution = enabled;
+        self
+    }
+
+    /// Disassemble each compiled chunk to the trace output before running it.
+    pub fn print_code(mut self, enabled: bool) -> Self {
+        self.print_code = enabled;
+        self
+    }
+
+    /// Sink for the execution trace and code listings.
+    pub fn trace_output(mut self, output: impl Write + 'static) -> Self {
+        self.trace_output = Box::new(output);
+        self
+    }
+
+    /// Sink for everything the script itself prints.
+    pub fn output(mut self, output: impl Write + 'static) -> Self {
+        self.output = Box::new(output);
+        self
+    }
+
+    /// Sink for compile and runtime error reports.
+    pub fn diagnostics(mut self, diagnostics: impl Write + 'static) -> Self {
+        self.diagnostics = Box::new(diagnostics);
+        self
+    }
+
+    /// Give scripts the `io` module for files and standard input. Turn off
+    /// to sandbox scripts from the host.
+    pub fn io(mut self, enabled: bool) -> Self {
+        self.io = enabled;
+        self
+    }
+
+    /// Starting seed for the `random` module, so runs can be repeated.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Source of the time for the `time` module and the default seed.
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Source for lines read by scripts instead of standard input.
+    pub fn input(mut self, input: impl BufRead + 'static) -> Self {
+        self.input = Some(Box::new(input));
+        self
+    }
+}
+
+impl Default for VmConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An in-memory sink that can be handed to a `VmConfig` while a clone is
+/// kept to read back what was written.
+#[derive(Debug, Clone, Default)]
+pub struct SharedBuffer {
+    buffer: Rc<RefCell<Vec<u8>>>,
+}
+
+impl SharedBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.buffer.borrow()).into_owned()
+    }
+
+    pub fn clear(&self) {
+        self.buffer.borrow_mut().clear();
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
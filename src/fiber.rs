@@ -0,0 +1,78 @@
+use crate::value::Value;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CallFrame {
+    pub closure: usize,
+    pub function: usize,
+    pub ip: usize,
+    pub slot_base: usize,
+    // Called by `for ... in`, which also wants a "has more" flag.
+    pub iterator: bool,
+    pub generator: Option<usize>,
+}
+
+impl CallFrame {
+    pub fn new(closure: usize, function: usize, slot_base: usize) -> Self {
+        Self {
+            closure,
+            function,
+            ip: 0,
+            slot_base,
+            iterator: false,
+            generator: None,
+        }
+    }
+}
+
+/// An active `try` block: where to resume and how much of the stack and
+/// call frames to keep when something is thrown inside it.
+#[derive(Debug, Clone, Copy)]
+pub struct Handler {
+    pub frame_count: usize,
+    pub stack_len: usize,
+    pub catch_ip: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FiberState {
+    New,
+    Suspended,
+    Running,
+    Done,
+}
+
+/// The running fiber's context lives in the VM, so these fields are only
+/// filled in while the fiber is switched out.
+#[derive(Debug, Clone)]
+pub struct Fiber {
+    pub frames: Vec<CallFrame>,
+    pub stack: Vec<Value>,
+    pub open_upvalues: Vec<usize>,
+    pub handlers: Vec<Handler>,
+    pub state: FiberState,
+    pub caller: Option<usize>,
+}
+
+impl Fiber {
+    pub fn new(closure: Value, function: usize) -> Self {
+        Self {
+            frames: vec![CallFrame::new(closure.as_obj(), function, 0)],
+            stack: vec![closure],
+            open_upvalues: Vec::new(),
+            handlers: Vec::new(),
+            state: FiberState::New,
+            caller: None,
+        }
+    }
+
+    pub fn main() -> Self {
+        Self {
+            frames: Vec::new(),
+            stack: Vec::new(),
+            open_upvalues: Vec::new(),
+            handlers: Vec::new(),
+            state: FiberState::Running,
+            caller: None,
+        }
+    }
+}
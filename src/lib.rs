@@ -0,0 +1,14 @@
+mod arena;
+mod chunk;
+pub mod clock;
+mod compiler;
+pub mod config;
+mod fiber;
+mod map;
+mod module;
+mod native;
+mod rng;
+mod scanner;
+mod token;
+mod value;
+pub mod vm;
@@ -1,23 +1,23 @@
-mod arena;
-mod chunk;
-mod compiler;
-mod scanner;
-mod token;
-mod value;
-mod vm;
+use std::{env, fs::File, io::BufWriter, process::ExitCode};
 
-use std::{env, process::ExitCode};
-
-use vm::*;
+use blox2::{config::*, vm::*};
 
 fn main() -> ExitCode {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().collect();
+
+    let config = match config_from_env().and_then(|config| seed_from_args(config, &mut args)) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
 
-    let mut vm = Vm::new();
+    let mut vm = Vm::with_config(config);
 
     let result = match args.len() {
         1 => vm.repl(),
-        _ => vm.run_file(&args[1]),
+        _ => vm.run_file(&args[1], &args[2..]),
     };
 
     match result {
@@ -25,3 +25,33 @@ fn main() -> ExitCode {
         _ => ExitCode::FAILURE,
     }
 }
+
+fn config_from_env() -> Result<VmConfig, String> {
+    let is_set = |name: &str| env::var(name).is_ok_and(|var| var == "1");
+    let config = VmConfig::new()
+        .trace_execution(is_set("DEBUG_TRACE_EXECUTION"))
+        .print_code(is_set("DEBUG_PRINT_CODE"));
+
+    match env::var("DEBUG_TRACE_FILE") {
+        Ok(path) => match File::create(&path) {
+            Ok(file) => Ok(config.trace_output(BufWriter::new(file))),
+            Err(e) => Err(format!("Failed to open trace file at {path}: {e}")),
+        },
+        Err(_) => Ok(config),
+    }
+}
+
+fn seed_from_args(config: VmConfig, args: &mut Vec<String>) -> Result<VmConfig, String> {
+    if args.get(1).is_none_or(|arg| arg != "--seed") {
+        return Ok(config);
+    }
+
+    let seed = args
+        .get(2)
+        .ok_or_else(|| String::from("Expected a number after --seed."))?;
+    let seed = seed
+        .parse()
+        .map_err(|_| format!("Seed must be a non-negative integer but got '{seed}'."))?;
+    args.drain(1..3);
+    Ok(config.seed(seed))
+}
@@ -0,0 +1,83 @@
+use std::collections::HashMap;
+
+use crate::{arena::Arena, value::*};
+
+/// A hashable stand-in for a `Value` so strings are keyed by their contents
+/// rather than by where they live in the arena.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MapKey {
+    Nil,
+    Bool(bool),
+    Number(u64),
+    Str(String),
+}
+
+impl MapKey {
+    pub fn from_value(value: &Value, objects: &Arena<Obj>) -> Result<Self, String> {
+        match value {
+            Value::Nil => Ok(Self::Nil),
+            Value::Bool(b) => Ok(Self::Bool(*b)),
+            Value::Number(n) if n.is_nan() => Err(String::from("Map key cannot be NaN.")),
+            // Normalise -0 so it finds the same entry as 0.
+            Value::Number(n) => Ok(Self::Number((n + 0.0).to_bits())),
+            Value::Obj(index) => match objects.get(*index) {
+                Obj::Str(s) => Ok(Self::Str(s.clone())),
+                _ => Err(String::from(
+                    "Map keys must be strings, numbers, booleans or nil.",
+                )),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Map {
+    entries: Vec<(Value, Value)>,
+    positions: HashMap<MapKey, usize>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, key: &MapKey) -> Option<Value> {
+        self.positions
+            .get(key)
+            .map(|position| self.entries[*position].1)
+    }
+
+    pub fn contains(&self, key: &MapKey) -> bool {
+        self.positions.contains_key(key)
+    }
+
+    pub fn insert(&mut self, key: MapKey, key_value: Value, value: Value) {
+        match self.positions.get(&key) {
+            Some(position) => self.entries[*position].1 = value,
+            None => {
+                self.positions.insert(key, self.entries.len());
+                self.entries.push((key_value, value));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, key: &MapKey) -> Option<Value> {
+        let position = self.positions.remove(key)?;
+        let (_, value) = self.entries.remove(position);
+        for later in self.positions.values_mut() {
+            if *later > position {
+                *later -= 1;
+            }
+        }
+
+        Some(value)
+    }
+
+    pub fn entries(&self) -> &[(Value, Value)] {
+        &self.entries
+    }
+}
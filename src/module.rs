@@ -0,0 +1,61 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::value::Value;
+
+/// A script file with its own globals. Module 0 is the script the VM was
+/// started with, or the REPL.
+#[derive(Debug, Clone)]
+pub struct Module {
+    pub name: String,
+    pub path: Option<PathBuf>,
+    pub globals: HashMap<String, Value>,
+    pub exports: HashSet<String>,
+    // False while its top-level code runs, which is how cycles show up.
+    pub loaded: bool,
+}
+
+impl Module {
+    pub fn new(path: Option<PathBuf>) -> Self {
+        let name = path
+            .as_deref()
+            .and_then(Path::file_name)
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("script"));
+
+        Self {
+            name,
+            path,
+            globals: HashMap::new(),
+            exports: HashSet::new(),
+            loaded: false,
+        }
+    }
+
+    pub fn builtin(name: &str, globals: HashMap<String, Value>) -> Self {
+        Self {
+            name: name.to_string(),
+            path: None,
+            exports: globals.keys().cloned().collect(),
+            globals,
+            loaded: true,
+        }
+    }
+
+    pub fn directory(&self) -> PathBuf {
+        self.path
+            .as_deref()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    }
+
+    pub fn export(&self, name: &str) -> Result<Value, String> {
+        match self.globals.get(name) {
+            Some(value) if self.exports.contains(name) => Ok(*value),
+            _ => Err(format!("Module '{}' does not export '{name}'.", self.name)),
+        }
+    }
+}
@@ -0,0 +1,158 @@
+pub mod io;
+pub mod json;
+pub mod math;
+pub mod random;
+pub mod string;
+pub mod time;
+
+use crate::{
+    fiber::{Fiber, FiberState},
+    value::*,
+    vm::Vm,
+};
+
+pub fn argc(vm: &mut Vm, _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(vm.args().len() as f64))
+}
+
+pub fn argv(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let index = match args[0] {
+        Value::Number(n) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+        _ => {
+            return Err(String::from(
+                "Argument index must be a non-negative integer.",
+            ));
+        }
+    };
+
+    match vm.args().get(index) {
+        Some(arg) => {
+            let arg = arg.clone();
+            Ok(vm.alloc(Obj::Str(arg)))
+        }
+        None => Err(format!(
+            "Argument index {index} is out of range for {} arguments.",
+            vm.args().len()
+        )),
+    }
+}
+
+pub fn len(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    match vm.object(args[0]) {
+        Some(Obj::List(list)) => Ok(Value::Number(list.len() as f64)),
+        Some(Obj::Map(map)) => Ok(Value::Number(map.len() as f64)),
+        Some(Obj::Str(s)) => Ok(Value::Number(s.chars().count() as f64)),
+        Some(Obj::Range(range)) => Ok(Value::Number(range.len() as f64)),
+        _ => Err(String::from(
+            "Can only take the length of a list, map, string or range.",
+        )),
+    }
+}
+
+pub fn push(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    match vm.object_mut(args[0]) {
+        Some(Obj::List(list)) => {
+            list.push(args[1]);
+            Ok(Value::Nil)
+        }
+        _ => Err(String::from("Can only push onto a list.")),
+    }
+}
+
+pub fn pop(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    match vm.object_mut(args[0]) {
+        Some(Obj::List(list)) => list
+            .pop()
+            .ok_or_else(|| String::from("Cannot pop from an empty list.")),
+        _ => Err(String::from("Can only pop from a list.")),
+    }
+}
+
+pub fn has(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let key = vm.map_key(args[1])?;
+    match vm.object(args[0]) {
+        Some(Obj::Map(map)) => Ok(Value::Bool(map.contains(&key))),
+        _ => Err(String::from("Can only check keys of a map.")),
+    }
+}
+
+pub fn remove(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let key = vm.map_key(args[1])?;
+    match vm.object_mut(args[0]) {
+        Some(Obj::Map(map)) => Ok(map.remove(&key).unwrap_or(Value::Nil)),
+        _ => Err(String::from("Can only remove keys from a map.")),
+    }
+}
+
+pub fn keys(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let keys = map_entries(vm, args[0])?
+        .iter()
+        .map(|(key, _)| *key)
+        .collect();
+    Ok(vm.alloc(Obj::List(keys)))
+}
+
+pub fn values(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let values = map_entries(vm, args[0])?
+        .iter()
+        .map(|(_, value)| *value)
+        .collect();
+    Ok(vm.alloc(Obj::List(values)))
+}
+
+pub fn entries(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let entries = map_entries(vm, args[0])?;
+    let pairs = entries
+        .into_iter()
+        .map(|(key, value)| vm.alloc(Obj::List(vec![key, value])))
+        .collect();
+    Ok(vm.alloc(Obj::List(pairs)))
+}
+
+pub fn error(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let message = vm.format(args[0]);
+    Ok(vm.alloc(Obj::Error(message)))
+}
+
+fn map_entries(vm: &Vm, value: Value) -> Result<Vec<(Value, Value)>, String> {
+    match vm.object(value) {
+        Some(Obj::Map(map)) => Ok(map.entries().to_vec()),
+        _ => Err(String::from("Expected a map.")),
+    }
+}
+
+pub fn fiber(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let Some(Obj::Closure(closure)) = vm.object(args[0]) else {
+        return Err(String::from("A fiber runs a function."));
+    };
+
+    let function = closure.function;
+    match vm.object(Value::Obj(function)) {
+        Some(Obj::Function(f)) if f.is_generator => {
+            Err(String::from("A fiber cannot run a generator function."))
+        }
+        Some(Obj::Function(f)) if f.arity != 0 => {
+            Err(String::from("A fiber runs a function with no parameters."))
+        }
+        _ => Ok(vm.alloc(Obj::Fiber(Fiber::new(args[0], function)))),
+    }
+}
+
+/// Runs a fiber until it suspends or finishes. The optional second argument
+/// is what the fiber's pending `suspend` call returns.
+pub fn resume(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    vm.resume_fiber(args[0], args[1])?;
+    Ok(Value::Nil)
+}
+
+pub fn suspend(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    vm.suspend_fiber(args[0])?;
+    Ok(Value::Nil)
+}
+
+pub fn is_done(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    match vm.object(args[0]) {
+        Some(Obj::Fiber(fiber)) => Ok(Value::Bool(fiber.state == FiberState::Done)),
+        _ => Err(String::from("Can only check whether a fiber is done.")),
+    }
+}
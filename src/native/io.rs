@@ -0,0 +1,80 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+};
+
+use crate::{
+    value::{Obj, Value},
+    vm::Vm,
+};
+
+pub fn read_line(vm: &mut Vm, _args: &[Value]) -> Result<Value, String> {
+    match vm.read_line() {
+        Ok(Some(line)) => Ok(vm.alloc(Obj::Str(line))),
+        Ok(None) => Ok(Value::Nil),
+        Err(e) => Err(format!("Could not read input: {e}.")),
+    }
+}
+
+pub fn read_file(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let path = path(vm, args[0])?;
+    let contents =
+        fs::read_to_string(&path).map_err(|e| format!("Could not read file '{path}': {e}."))?;
+    Ok(vm.alloc(Obj::Str(contents)))
+}
+
+pub fn write_file(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let path = path(vm, args[0])?;
+    let contents = contents(vm, args[1])?;
+    fs::write(&path, contents).map_err(|e| format!("Could not write file '{path}': {e}."))?;
+    Ok(Value::Nil)
+}
+
+pub fn append_file(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let path = path(vm, args[0])?;
+    let contents = contents(vm, args[1])?;
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(contents.as_bytes()))
+        .map_err(|e| format!("Could not append to file '{path}': {e}."))?;
+    Ok(Value::Nil)
+}
+
+pub fn exists(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let path = path(vm, args[0])?;
+    Ok(Value::Bool(fs::exists(&path).unwrap_or(false)))
+}
+
+pub fn list_dir(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let path = path(vm, args[0])?;
+    let mut names = fs::read_dir(&path)
+        .and_then(|entries| {
+            entries
+                .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+                .collect::<Result<Vec<String>, _>>()
+        })
+        .map_err(|e| format!("Could not list directory '{path}': {e}."))?;
+    names.sort();
+
+    let names = names
+        .into_iter()
+        .map(|name| vm.alloc(Obj::Str(name)))
+        .collect();
+    Ok(vm.alloc(Obj::List(names)))
+}
+
+fn path(vm: &Vm, value: Value) -> Result<String, String> {
+    match vm.object(value) {
+        Some(Obj::Str(path)) => Ok(path.clone()),
+        _ => Err(String::from("Path must be a string.")),
+    }
+}
+
+fn contents(vm: &Vm, value: Value) -> Result<String, String> {
+    match vm.object(value) {
+        Some(Obj::Str(contents)) => Ok(contents.clone()),
+        _ => Err(String::from("File contents must be a string.")),
+    }
+}
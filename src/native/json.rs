@@ -0,0 +1,392 @@
+use crate::{
+    map::{Map, MapKey},
+    value::{MAX_DEPTH, Obj, Value},
+    vm::Vm,
+};
+
+const MAX_INDENT: usize = 10;
+
+pub fn parse(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let Some(Obj::Str(text)) = vm.object(args[0]) else {
+        return Err(String::from("Can only parse a string as JSON."));
+    };
+
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        position: 0,
+    };
+    let value = parser.value(vm, 0)?;
+    parser.skip_whitespace();
+    match parser.chars.get(parser.position) {
+        None => Ok(value),
+        Some(_) => Err(parser.error("unexpected text after the value")),
+    }
+}
+
+pub fn stringify(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let indent = match args[1] {
+        Value::Nil => 0,
+        Value::Number(n) if n.fract() == 0.0 && n >= 0.0 && n <= MAX_INDENT as f64 => n as usize,
+        _ => {
+            return Err(format!("Indent must be an integer from 0 to {MAX_INDENT}."));
+        }
+    };
+
+    let mut writer = Writer {
+        vm,
+        indent,
+        visiting: Vec::new(),
+        text: String::new(),
+    };
+    writer.value(args[0], 0)?;
+    let text = writer.text;
+    Ok(vm.alloc(Obj::Str(text)))
+}
+
+/// Escapes a string for use between double quotes in JSON.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn value(&mut self, vm: &mut Vm, depth: usize) -> Result<Value, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("nesting is too deep"));
+        }
+
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.object(vm, depth),
+            Some('[') => self.array(vm, depth),
+            Some('"') => {
+                let string = self.string()?;
+                Ok(vm.alloc(Obj::Str(string)))
+            }
+            Some('-' | '0'..='9') => self.number(),
+            Some('t') => self.literal("true", Value::Bool(true)),
+            Some('f') => self.literal("false", Value::Bool(false)),
+            Some('n') => self.literal("null", Value::Nil),
+            Some(_) => Err(self.error("expected a value")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn object(&mut self, vm: &mut Vm, depth: usize) -> Result<Value, String> {
+        self.position += 1;
+        let mut map = Map::new();
+        self.skip_whitespace();
+        if self.eat('}') {
+            return Ok(vm.alloc(Obj::Map(map)));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if !self.eat(':') {
+                return Err(self.error("expected ':' after key"));
+            }
+
+            let value = self.value(vm, depth + 1)?;
+            let key_value = vm.alloc(Obj::Str(key.clone()));
+            map.insert(MapKey::Str(key), key_value, value);
+
+            self.skip_whitespace();
+            if self.eat('}') {
+                return Ok(vm.alloc(Obj::Map(map)));
+            }
+            if !self.eat(',') {
+                return Err(self.error("expected ',' or '}'"));
+            }
+        }
+    }
+
+    fn array(&mut self, vm: &mut Vm, depth: usize) -> Result<Value, String> {
+        self.position += 1;
+        let mut list = Vec::new();
+        self.skip_whitespace();
+        if self.eat(']') {
+            return Ok(vm.alloc(Obj::List(list)));
+        }
+
+        loop {
+            list.push(self.value(vm, depth + 1)?);
+            self.skip_whitespace();
+            if self.eat(']') {
+                return Ok(vm.alloc(Obj::List(list)));
+            }
+            if !self.eat(',') {
+                return Err(self.error("expected ',' or ']'"));
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.position += 1;
+        let mut string = String::new();
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(string),
+                Some('\\') => string.push(self.escape()?),
+                Some(c) if (c as u32) < 0x20 => {
+                    return Err(self.error("control character in string"));
+                }
+                Some(c) => string.push(c),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, String> {
+        match self.advance() {
+            Some('"') => Ok('"'),
+            Some('\\') => Ok('\\'),
+            Some('/') => Ok('/'),
+            Some('b') => Ok('\u{8}'),
+            Some('f') => Ok('\u{c}'),
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('u') => {
+                let high = self.hex()?;
+                // Characters outside the Basic Multilingual Plane come as a
+                // surrogate pair of escapes.
+                let code = if (0xd800..0xdc00).contains(&high) {
+                    if !(self.eat('\\') && self.eat('u')) {
+                        return Err(self.error("unpaired surrogate"));
+                    }
+                    let low = self.hex()?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(self.error("unpaired surrogate"));
+                    }
+                    0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                } else {
+                    high
+                };
+                char::from_u32(code).ok_or_else(|| self.error("unpaired surrogate"))
+            }
+            _ => Err(self.error("invalid escape")),
+        }
+    }
+
+    fn hex(&mut self) -> Result<u32, String> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self
+                .advance()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| self.error("expected four hex digits"))?;
+            code = code * 16 + digit;
+        }
+
+        Ok(code)
+    }
+
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.position;
+        self.eat('-');
+        if !self.eat('0') && !self.digits() {
+            return Err(self.error("expected a digit"));
+        }
+        if self.eat('.') && !self.digits() {
+            return Err(self.error("expected a digit after '.'"));
+        }
+        if self.eat('e') || self.eat('E') {
+            _ = self.eat('+') || self.eat('-');
+            if !self.digits() {
+                return Err(self.error("expected a digit in exponent"));
+            }
+        }
+
+        let text: String = self.chars[start..self.position].iter().collect();
+        text.parse()
+            .map(Value::Number)
+            .map_err(|_| self.error("invalid number"))
+    }
+
+    fn digits(&mut self) -> bool {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        self.position > start
+    }
+
+    fn literal(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for expected in word.chars() {
+            if !self.eat(expected) {
+                return Err(self.error("expected a value"));
+            }
+        }
+
+        Ok(value)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self
+            .peek()
+            .is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r'))
+        {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += 1;
+        Some(c)
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("Invalid JSON at position {}: {message}.", self.position)
+    }
+}
+
+struct Writer<'a> {
+    vm: &'a Vm,
+    indent: usize,
+    visiting: Vec<usize>,
+    text: String,
+}
+
+impl Writer<'_> {
+    fn value(&mut self, value: Value, depth: usize) -> Result<(), String> {
+        let index = match value {
+            Value::Nil => {
+                self.text.push_str("null");
+                return Ok(());
+            }
+            Value::Bool(b) => {
+                self.text.push_str(if b { "true" } else { "false" });
+                return Ok(());
+            }
+            Value::Number(n) if !n.is_finite() => {
+                return Err(format!("Cannot serialize {n} to JSON."));
+            }
+            Value::Number(n) => {
+                self.text.push_str(&n.to_string());
+                return Ok(());
+            }
+            Value::Obj(index) => index,
+        };
+
+        if depth > MAX_DEPTH {
+            return Err(String::from(
+                "Cannot serialize to JSON: nesting is too deep.",
+            ));
+        }
+
+        if self.visiting.contains(&index) {
+            return Err(String::from(
+                "Cannot serialize a value that contains itself to JSON.",
+            ));
+        }
+
+        let vm = self.vm;
+        match vm.object(value) {
+            Some(Obj::Str(string)) => self.string(string),
+            Some(Obj::List(list)) => {
+                self.visiting.push(index);
+                self.text.push('[');
+                for (position, element) in list.iter().enumerate() {
+                    self.separator(position, depth + 1);
+                    self.value(*element, depth + 1)?;
+                }
+                self.close(list.is_empty(), depth);
+                self.text.push(']');
+                self.visiting.pop();
+            }
+            Some(Obj::Map(map)) => {
+                self.visiting.push(index);
+                self.text.push('{');
+                for (position, (key, value)) in map.entries().iter().enumerate() {
+                    self.separator(position, depth + 1);
+                    match vm.object(*key) {
+                        Some(Obj::Str(key)) => self.string(key),
+                        _ if key.is_number() => self.string(&vm.format(*key)),
+                        _ => {
+                            return Err(String::from(
+                                "JSON object keys must be strings or numbers.",
+                            ));
+                        }
+                    }
+                    self.text.push(':');
+                    if self.indent > 0 {
+                        self.text.push(' ');
+                    }
+                    self.value(*value, depth + 1)?;
+                }
+                self.close(map.len() == 0, depth);
+                self.text.push('}');
+                self.visiting.pop();
+            }
+            _ => return Err(format!("Cannot serialize {} to JSON.", vm.format(value))),
+        }
+
+        Ok(())
+    }
+
+    fn string(&mut self, string: &str) {
+        self.text.push('"');
+        self.text.push_str(&escape(string));
+        self.text.push('"');
+    }
+
+    fn separator(&mut self, position: usize, depth: usize) {
+        if position > 0 {
+            self.text.push(',');
+        }
+        self.newline(depth);
+    }
+
+    fn close(&mut self, empty: bool, depth: usize) {
+        if !empty {
+            self.newline(depth);
+        }
+    }
+
+    fn newline(&mut self, depth: usize) {
+        if self.indent > 0 {
+            self.text.push('\n');
+            let width = self
+                .indent
+                .checked_mul(depth)
+                .expect("Indent and depth are both limited.");
+            self.text.push_str(&" ".repeat(width));
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use crate::{value::Value, vm::Vm};
+
+pub fn sqrt(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::sqrt)
+}
+
+pub fn pow(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number(args[0])?.powf(number(args[1])?)))
+}
+
+pub fn abs(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::abs)
+}
+
+pub fn floor(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::floor)
+}
+
+pub fn ceil(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::ceil)
+}
+
+/// Rounds halfway cases away from zero.
+pub fn round(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::round)
+}
+
+pub fn sin(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::sin)
+}
+
+pub fn cos(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::cos)
+}
+
+pub fn tan(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::tan)
+}
+
+pub fn asin(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::asin)
+}
+
+pub fn acos(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::acos)
+}
+
+pub fn atan(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::atan)
+}
+
+pub fn atan2(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number(args[0])?.atan2(number(args[1])?)))
+}
+
+pub fn log(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::ln)
+}
+
+pub fn log2(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::log2)
+}
+
+pub fn log10(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::log10)
+}
+
+pub fn exp(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    unary(args, f64::exp)
+}
+
+pub fn min(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number(args[0])?.min(number(args[1])?)))
+}
+
+pub fn max(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(number(args[0])?.max(number(args[1])?)))
+}
+
+pub fn isnan(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(number(args[0])?.is_nan()))
+}
+
+pub fn isinf(_vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Bool(number(args[0])?.is_infinite()))
+}
+
+fn unary(args: &[Value], function: fn(f64) -> f64) -> Result<Value, String> {
+    Ok(Value::Number(function(number(args[0])?)))
+}
+
+fn number(value: Value) -> Result<f64, String> {
+    match value {
+        Value::Number(n) => Ok(n),
+        _ => Err(String::from("Math functions take numbers.")),
+    }
+}
@@ -0,0 +1,71 @@
+use crate::{
+    value::{Obj, Value},
+    vm::Vm,
+};
+
+// Past 2^53 not every integer is representable.
+const MAX_SPAN: f64 = (1u64 << 53) as f64;
+
+pub fn seed(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    match args[0] {
+        Value::Number(n) if n.fract() == 0.0 && n >= 0.0 && n <= u64::MAX as f64 => {
+            vm.reseed(n as u64);
+            Ok(Value::Nil)
+        }
+        _ => Err(String::from("Seed must be a non-negative integer.")),
+    }
+}
+
+pub fn float(vm: &mut Vm, _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(vm.rng().next_f64()))
+}
+
+pub fn int(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let (min, max) = match (args[0], args[1]) {
+        (Value::Number(min), Value::Number(max)) if min.fract() == 0.0 && max.fract() == 0.0 => {
+            (min, max)
+        }
+        _ => return Err(String::from("Random bounds must be integers.")),
+    };
+
+    if min > max {
+        return Err(format!("Random range {min}..={max} is empty."));
+    }
+
+    if max - min >= MAX_SPAN {
+        return Err(format!("Random range {min}..={max} is too large."));
+    }
+
+    let span = (max - min) as u64 + 1;
+    let offset = vm.rng().below(span).expect("Range is not empty.");
+    Ok(Value::Number(min + offset as f64))
+}
+
+pub fn choice(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let Some(Obj::List(list)) = vm.object(args[0]) else {
+        return Err(String::from("Can only choose from a list."));
+    };
+
+    let list = list.clone();
+    let Some(index) = vm.rng().below(list.len() as u64) else {
+        return Err(String::from("Cannot choose from an empty list."));
+    };
+    Ok(list[index as usize])
+}
+
+pub fn shuffle(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let Some(Obj::List(list)) = vm.object(args[0]) else {
+        return Err(String::from("Can only shuffle a list."));
+    };
+
+    let mut list = list.clone();
+    for i in (1..list.len()).rev() {
+        let j = vm.rng().below(i as u64 + 1).expect("Range is not empty.") as usize;
+        list.swap(i, j);
+    }
+
+    if let Some(Obj::List(target)) = vm.object_mut(args[0]) {
+        *target = list;
+    }
+    Ok(Value::Nil)
+}
@@ -0,0 +1,166 @@
+use crate::{
+    value::{Obj, Value},
+    vm::Vm,
+};
+
+// The longest string `repeat` will build, in characters.
+const MAX_LENGTH: usize = 1 << 24;
+
+pub fn length(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let string = string(vm, args[0])?;
+    Ok(Value::Number(string.chars().count() as f64))
+}
+
+pub fn slice(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let chars: Vec<char> = string(vm, args[0])?.chars().collect();
+    let (start, end) = (index(args[1])?, index(args[2])?);
+    if start > end || end > chars.len() {
+        return Err(format!(
+            "Slice {start}..{end} is out of bounds for length {}.",
+            chars.len()
+        ));
+    }
+
+    let slice = chars[start..end].iter().collect();
+    Ok(vm.alloc(Obj::Str(slice)))
+}
+
+pub fn find(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let haystack = string(vm, args[0])?;
+    let needle = string(vm, args[1])?;
+    Ok(haystack
+        .find(&needle)
+        .map(|byte| Value::Number(haystack[..byte].chars().count() as f64))
+        .unwrap_or(Value::Nil))
+}
+
+pub fn replace(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let string = string(vm, args[0])?;
+    let from = self::string(vm, args[1])?;
+    let to = self::string(vm, args[2])?;
+    if from.is_empty() {
+        return Err(String::from("Cannot replace an empty string."));
+    }
+
+    Ok(vm.alloc(Obj::Str(string.replace(&from, &to))))
+}
+
+/// An empty separator splits into single characters.
+pub fn split(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let string = string(vm, args[0])?;
+    let separator = self::string(vm, args[1])?;
+    let parts: Vec<String> = if separator.is_empty() {
+        string.chars().map(String::from).collect()
+    } else {
+        string.split(&separator).map(String::from).collect()
+    };
+
+    let parts = parts
+        .into_iter()
+        .map(|part| vm.alloc(Obj::Str(part)))
+        .collect();
+    Ok(vm.alloc(Obj::List(parts)))
+}
+
+pub fn join(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let Some(Obj::List(list)) = vm.object(args[0]) else {
+        return Err(String::from("Can only join a list."));
+    };
+    let list = list.clone();
+    let separator = string(vm, args[1])?;
+    let parts: Vec<String> = list.into_iter().map(|value| vm.format(value)).collect();
+    Ok(vm.alloc(Obj::Str(parts.join(&separator))))
+}
+
+pub fn trim(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let string = string(vm, args[0])?;
+    Ok(vm.alloc(Obj::Str(string.trim().to_string())))
+}
+
+pub fn upper(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let string = string(vm, args[0])?;
+    Ok(vm.alloc(Obj::Str(string.to_uppercase())))
+}
+
+pub fn lower(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let string = string(vm, args[0])?;
+    Ok(vm.alloc(Obj::Str(string.to_lowercase())))
+}
+
+pub fn starts_with(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let string = string(vm, args[0])?;
+    let prefix = self::string(vm, args[1])?;
+    Ok(Value::Bool(string.starts_with(&prefix)))
+}
+
+pub fn ends_with(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let string = string(vm, args[0])?;
+    let suffix = self::string(vm, args[1])?;
+    Ok(Value::Bool(string.ends_with(&suffix)))
+}
+
+pub fn code(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let string = string(vm, args[0])?;
+    let index = index(args[1])?;
+    match string.chars().nth(index) {
+        Some(c) => Ok(Value::Number(c as u32 as f64)),
+        None => Err(format!(
+            "String index {index} is out of bounds for length {}.",
+            string.chars().count()
+        )),
+    }
+}
+
+pub fn from_code(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let c = match args[0] {
+        Value::Number(n) if n.fract() == 0.0 && n >= 0.0 && n <= u32::MAX as f64 => {
+            char::from_u32(n as u32)
+        }
+        _ => None,
+    };
+
+    match c {
+        Some(c) => Ok(vm.alloc(Obj::Str(c.to_string()))),
+        None => Err(format!(
+            "{} is not a valid character code.",
+            vm.format(args[0])
+        )),
+    }
+}
+
+pub fn repeat(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let string = string(vm, args[0])?;
+    let count = index(args[1])?;
+    match string.chars().count().checked_mul(count) {
+        Some(length) if length <= MAX_LENGTH => Ok(vm.alloc(Obj::Str(string.repeat(count)))),
+        _ => Err(String::from("Repeated string is too long.")),
+    }
+}
+
+pub fn to_number(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let string = string(vm, args[0])?;
+    let text = string.trim();
+    let is_decimal = text
+        .chars()
+        .all(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E'));
+    Ok(match text.parse::<f64>() {
+        Ok(n) if is_decimal => Value::Number(n),
+        _ => Value::Nil,
+    })
+}
+
+fn string(vm: &Vm, value: Value) -> Result<String, String> {
+    match vm.object(value) {
+        Some(Obj::Str(string)) => Ok(string.clone()),
+        _ => Err(String::from("String functions take strings.")),
+    }
+}
+
+fn index(value: Value) -> Result<usize, String> {
+    match value {
+        Value::Number(n) if n.fract() == 0.0 && n >= 0.0 => Ok(n as usize),
+        _ => Err(String::from(
+            "String indices and counts must be non-negative integers.",
+        )),
+    }
+}
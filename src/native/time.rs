@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+use crate::{
+    value::{Obj, Value},
+    vm::Vm,
+};
+
+const SECONDS_PER_DAY: i64 = 86_400;
+
+pub fn now(vm: &mut Vm, _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(vm.clock().now()))
+}
+
+pub fn monotonic(vm: &mut Vm, _args: &[Value]) -> Result<Value, String> {
+    Ok(Value::Number(vm.clock().monotonic()))
+}
+
+pub fn sleep(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let seconds = match args[0] {
+        Value::Number(seconds) if seconds >= 0.0 && seconds.is_finite() => seconds,
+        _ => {
+            return Err(String::from(
+                "Sleep duration must be a non-negative number of seconds.",
+            ));
+        }
+    };
+
+    let duration = Duration::try_from_secs_f64(seconds)
+        .map_err(|_| String::from("Sleep duration is too long."))?;
+    vm.clock().sleep(duration);
+    Ok(Value::Nil)
+}
+
+pub fn format(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let Value::Number(timestamp) = args[0] else {
+        return Err(String::from("Timestamp must be a number."));
+    };
+
+    let millis = (timestamp * 1000.0).round();
+    // Years 0 to 9999, the ones with a four-digit form.
+    if !(-62_167_219_200_000.0..253_402_300_800_000.0).contains(&millis) {
+        return Err(format!("Timestamp {timestamp} is out of range for a date."));
+    }
+
+    let millis = millis as i64;
+    let seconds = millis.div_euclid(1000);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+    let time = seconds.rem_euclid(SECONDS_PER_DAY);
+    let mut text = format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    );
+    if millis.rem_euclid(1000) != 0 {
+        text.push_str(&format!(".{:03}", millis.rem_euclid(1000)));
+    }
+    text.push('Z');
+    Ok(vm.alloc(Obj::Str(text)))
+}
+
+/// Reads an ISO-8601 date, with an optional time and UTC offset, as a
+/// timestamp. Dates without an offset are taken to be UTC.
+pub fn parse(vm: &mut Vm, args: &[Value]) -> Result<Value, String> {
+    let Some(Obj::Str(text)) = vm.object(args[0]) else {
+        return Err(String::from("Can only parse a string as a date."));
+    };
+
+    parse_iso(text)
+        .map(Value::Number)
+        .ok_or_else(|| format!("Invalid ISO-8601 date '{text}'."))
+}
+
+fn parse_iso(text: &str) -> Option<f64> {
+    let mut reader = Reader { text, position: 0 };
+    let year = reader.digits(4)?;
+    reader.expect('-')?;
+    let month = reader.digits(2)?;
+    reader.expect('-')?;
+    let day = reader.digits(2)?;
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+
+    let mut seconds = days_from_civil(year, month, day) as f64 * SECONDS_PER_DAY as f64;
+    if reader.expect('T').is_some() {
+        let hour = reader.digits(2)?;
+        reader.expect(':')?;
+        let minute = reader.digits(2)?;
+        let second = match reader.expect(':') {
+            Some(()) => reader.digits(2)?,
+            None => 0,
+        };
+        if hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+        seconds += (hour * 3600 + minute * 60 + second) as f64;
+
+        if reader.expect('.').is_some() {
+            let start = reader.position;
+            while reader.peek().is_some_and(|c| c.is_ascii_digit()) {
+                reader.position += 1;
+            }
+            if reader.position == start {
+                return None;
+            }
+            let fraction: f64 = format!("0.{}", &text[start..reader.position])
+                .parse()
+                .ok()?;
+            seconds += fraction;
+        }
+
+        match reader.peek() {
+            Some('Z') => reader.position += 1,
+            Some(sign @ ('+' | '-')) => {
+                reader.position += 1;
+                let hours = reader.digits(2)?;
+                reader.expect(':')?;
+                let minutes = reader.digits(2)?;
+                if hours > 23 || minutes > 59 {
+                    return None;
+                }
+                let offset = (hours * 3600 + minutes * 60) as f64;
+                // A local time ahead of UTC happened that much earlier.
+                seconds += if sign == '+' { -offset } else { offset };
+            }
+            _ => {}
+        }
+    }
+
+    (reader.position == text.len()).then_some(seconds)
+}
+
+struct Reader<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn digits(&mut self, count: usize) -> Option<i64> {
+        let digits = self.text.get(self.position..self.position + count)?;
+        if !digits.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        self.position += count;
+        digits.parse().ok()
+    }
+
+    fn expect(&mut self, expected: char) -> Option<()> {
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Some(())
+        } else {
+            None
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days from 1970-01-01 to a date in the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The date `days` days after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
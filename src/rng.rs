@@ -0,0 +1,41 @@
+/// SplitMix64: small, fast and good enough for scripts. The same seed
+/// always gives the same sequence, so runs can be repeated.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A float in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// An index in `0..len`, or `None` when `len` is zero. Rejects draws
+    /// from the uneven tail of the range to avoid modulo bias.
+    pub fn below(&mut self, len: u64) -> Option<u64> {
+        if len == 0 {
+            return None;
+        }
+
+        let limit = u64::MAX - u64::MAX % len;
+        loop {
+            let n = self.next_u64();
+            if n < limit {
+                return Some(n % len);
+            }
+        }
+    }
+}
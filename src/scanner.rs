@@ -6,6 +6,20 @@ pub struct Scanner {
     start: usize,
     current: usize,
     line: usize,
+    // One entry per `${` we are inside, counting the unclosed braces opened
+    // within that interpolated expression.
+    interpolations: Vec<usize>,
+    // `--` only scans as a decrement after an identifier or `]`.
+    previous: TokenType,
+}
+
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    start: usize,
+    current: usize,
+    line: usize,
+    interpolations: Vec<usize>,
+    previous: TokenType,
 }
 
 impl Scanner {
@@ -15,10 +29,36 @@ impl Scanner {
             start: 0,
             current: 0,
             line: 1,
+            interpolations: Vec::new(),
+            previous: TokenType::Eof,
+        }
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            start: self.start,
+            current: self.current,
+            line: self.line,
+            interpolations: self.interpolations.clone(),
+            previous: self.previous,
         }
     }
 
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.start = checkpoint.start;
+        self.current = checkpoint.current;
+        self.line = checkpoint.line;
+        self.interpolations = checkpoint.interpolations;
+        self.previous = checkpoint.previous;
+    }
+
     pub fn scan_token(&mut self) -> Token {
+        let token = self.next_token();
+        self.previous = token.typ;
+        token
+    }
+
+    fn next_token(&mut self) -> Token {
         self.skip_whitespace_and_comments();
 
         self.start = self.current;
@@ -31,23 +71,110 @@ impl Scanner {
             return self.identifier_token();
         }
 
-        if c.is_digit(10) {
+        if c.is_ascii_digit() {
             return self.number_token();
         }
 
         match c {
             '(' => self.make_token(TokenType::LeftParen),
             ')' => self.make_token(TokenType::RightParen),
-            '{' => self.make_token(TokenType::LeftBrace),
-            '}' => self.make_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.make_token(TokenType::LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string_body()
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.make_token(TokenType::RightBrace)
+                }
+                None => self.make_token(TokenType::RightBrace),
+            },
+            '[' => self.make_token(TokenType::LeftBracket),
+            ']' => self.make_token(TokenType::RightBracket),
             ';' => self.make_token(TokenType::SemiColon),
             ':' => self.make_token(TokenType::Colon),
             ',' => self.make_token(TokenType::Comma),
-            '.' => self.make_token(TokenType::Dot),
-            '-' => self.make_token(TokenType::Minus),
-            '+' => self.make_token(TokenType::Plus),
-            '/' => self.make_token(TokenType::Slash),
-            '*' => self.make_token(TokenType::Star),
+            '.' => {
+                let typ = if !self.check('.') {
+                    TokenType::Dot
+                } else if self.check('=') {
+                    TokenType::DotDotEqual
+                } else {
+                    TokenType::DotDot
+                };
+                self.make_token(typ)
+            }
+            '-' => {
+                let typ = if self.check('=') {
+                    TokenType::MinusEqual
+                } else if self.after_assignable() && self.check('-') {
+                    TokenType::MinusMinus
+                } else {
+                    TokenType::Minus
+                };
+                self.make_token(typ)
+            }
+            '+' => {
+                let typ = if self.check('=') {
+                    TokenType::PlusEqual
+                } else if self.check('+') {
+                    TokenType::PlusPlus
+                } else {
+                    TokenType::Plus
+                };
+                self.make_token(typ)
+            }
+            '/' => {
+                let typ = if self.check('=') {
+                    TokenType::SlashEqual
+                } else {
+                    TokenType::Slash
+                };
+                self.make_token(typ)
+            }
+            '%' => {
+                let typ = if self.check('=') {
+                    TokenType::PercentEqual
+                } else {
+                    TokenType::Percent
+                };
+                self.make_token(typ)
+            }
+            '&' => self.make_token(TokenType::Ampersand),
+            '|' => self.make_token(TokenType::Pipe),
+            '^' => self.make_token(TokenType::Caret),
+            '?' => {
+                let typ = if self.check('?') {
+                    TokenType::QuestionQuestion
+                } else {
+                    TokenType::Question
+                };
+                self.make_token(typ)
+            }
+            '*' => {
+                let typ = if self.check('*') {
+                    TokenType::StarStar
+                } else if self.check('=') {
+                    TokenType::StarEqual
+                } else {
+                    TokenType::Star
+                };
+                self.make_token(typ)
+            }
+            '~' => {
+                let typ = if self.check('/') {
+                    TokenType::TildeSlash
+                } else {
+                    TokenType::Tilde
+                };
+                self.make_token(typ)
+            }
             '!' => {
                 let typ = if self.check('=') {
                     TokenType::BangEqual
@@ -59,6 +186,8 @@ impl Scanner {
             '=' => {
                 let typ = if self.check('=') {
                     TokenType::EqualEqual
+                } else if self.check('>') {
+                    TokenType::Arrow
                 } else {
                     TokenType::Equal
                 };
@@ -67,6 +196,8 @@ impl Scanner {
             '<' => {
                 let typ = if self.check('=') {
                     TokenType::LessEqual
+                } else if self.check('<') {
+                    TokenType::LessLess
                 } else {
                     TokenType::Less
                 };
@@ -75,6 +206,8 @@ impl Scanner {
             '>' => {
                 let typ = if self.check('=') {
                     TokenType::GreaterEqual
+                } else if self.check('>') {
+                    TokenType::GreaterGreater
                 } else {
                     TokenType::Greater
                 };
@@ -130,36 +263,228 @@ impl Scanner {
     }
 
     fn number_token(&mut self) -> Token {
-        while !self.is_at_end() && self.peek().is_digit(10) {
-            self.advance();
+        if self.source[self.start] == '0' {
+            let radix = match self.peek_at(0).to_ascii_lowercase() {
+                'x' => Some(16),
+                'o' => Some(8),
+                'b' => Some(2),
+                _ => None,
+            };
+
+            if let Some(radix) = radix {
+                let prefix = self.advance();
+                if !self.peek_at(0).is_digit(radix) {
+                    return self.number_error(&format!("Expect digits after '0{prefix}'."));
+                }
+
+                return match self.digits(radix) {
+                    Ok(()) => self.end_number(),
+                    Err(message) => self.number_error(&message),
+                };
+            }
         }
 
-        if !self.is_at_end() && self.peek() == '.' && self.peek_next().is_digit(10) {
+        if let Err(message) = self.digits(10) {
+            return self.number_error(&message);
+        }
+
+        if self.peek_is('.') && self.peek_next().is_ascii_digit() {
             self.advance(); // consume the '.'.
-            while !self.is_at_end() && self.peek().is_digit(10) {
+            if let Err(message) = self.digits(10) {
+                return self.number_error(&message);
+            }
+        }
+
+        if self.peek_is('e') || self.peek_is('E') {
+            self.advance();
+            if self.peek_is('+') || self.peek_is('-') {
                 self.advance();
             }
+
+            if !self.peek_at(0).is_ascii_digit() {
+                return self.number_error("Expect digits in exponent.");
+            }
+
+            if let Err(message) = self.digits(10) {
+                return self.number_error(&message);
+            }
+        }
+
+        self.end_number()
+    }
+
+    fn digits(&mut self, radix: u32) -> Result<(), String> {
+        while !self.is_at_end() {
+            let c = self.peek();
+            if c.is_digit(radix) {
+                self.advance();
+            } else if c == '_' {
+                let previous = self.source[self.current - 1];
+                self.advance();
+                if !previous.is_digit(radix) || !self.peek_at(0).is_digit(radix) {
+                    return Err(String::from("Digit separator '_' must be between digits."));
+                }
+            } else if c.is_alphanumeric() && !(radix == 10 && (c == 'e' || c == 'E')) {
+                return Err(format!("Invalid digit '{c}' in number literal."));
+            } else {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn end_number(&mut self) -> Token {
+        if !self.is_at_end() && (self.peek().is_alphanumeric() || self.peek() == '_') {
+            let message = format!("Invalid character '{}' in number literal.", self.peek());
+            return self.number_error(&message);
         }
 
         self.make_token(TokenType::Number)
     }
 
+    fn number_error(&mut self, message: &str) -> Token {
+        while !self.is_at_end() && (self.peek().is_alphanumeric() || self.peek() == '_') {
+            self.advance();
+        }
+
+        self.error_token(message)
+    }
+
     fn string_token(&mut self) -> Token {
-        self.start += 1;
+        if self.peek_is('"') && self.peek_next() == '"' {
+            self.current += 2;
+            return self.raw_string_token();
+        }
+
+        self.string_body()
+    }
+
+    /// Stops early at `${`, giving the text so far as an `Interpolation`.
+    fn string_body(&mut self) -> Token {
+        let start_line = self.line;
+        let mut value = String::new();
+        let mut error = None;
         while !self.is_at_end() && self.peek() != '"' {
-            if self.peek() == '\n' {
-                self.line += 1;
+            match self.advance() {
+                '\\' => match self.escape_sequence() {
+                    Ok(c) => value.push(c),
+                    Err(message) => {
+                        error.get_or_insert(message);
+                    }
+                },
+                '\n' => {
+                    self.line += 1;
+                    value.push('\n');
+                }
+                '$' if self.peek_is('{') => {
+                    self.advance();
+                    self.interpolations.push(0);
+                    return match error {
+                        Some(message) => self.string_error(start_line, &message),
+                        None => self.make_string(TokenType::Interpolation, start_line, value),
+                    };
+                }
+                c => value.push(c),
             }
-            self.advance();
         }
 
         if self.is_at_end() {
-            return self.error_token("Unterminated string.");
+            return self.string_error(start_line, "Unterminated string.");
         }
 
         // Closing quote
         self.advance();
-        self.make_token(TokenType::Str)
+        match error {
+            Some(message) => self.string_error(start_line, &message),
+            None => self.make_string(TokenType::Str, start_line, value),
+        }
+    }
+
+    fn raw_string_token(&mut self) -> Token {
+        let start_line = self.line;
+        if self.peek_is('\n') {
+            self.advance();
+            self.line += 1;
+        }
+
+        let mut value = String::new();
+        loop {
+            if self.is_at_end() {
+                return self.string_error(start_line, "Unterminated raw string.");
+            }
+
+            if self.peek() == '"' && self.peek_next() == '"' && self.peek_at(2) == '"' {
+                self.current += 3;
+                return self.make_string(TokenType::Str, start_line, value);
+            }
+
+            let c = self.advance();
+            if c == '\n' {
+                self.line += 1;
+            }
+            value.push(c);
+        }
+    }
+
+    fn escape_sequence(&mut self) -> Result<char, String> {
+        if self.is_at_end() {
+            return Err(String::from("Unterminated escape sequence."));
+        }
+
+        match self.advance() {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '"' => Ok('"'),
+            '$' => Ok('$'),
+            '\'' => Ok('\''),
+            '\\' => Ok('\\'),
+            'u' => self.unicode_escape(),
+            '\n' => {
+                self.line += 1;
+                Err(String::from("Unknown escape sequence '\\' at end of line."))
+            }
+            c => Err(format!("Unknown escape sequence '\\{c}'.")),
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        if !self.check('{') {
+            return Err(String::from("Expect '{' after '\\u'."));
+        }
+
+        let mut digits = String::new();
+        while !self.is_at_end() && self.peek().is_ascii_hexdigit() {
+            digits.push(self.advance());
+        }
+
+        if !self.check('}') {
+            return Err(String::from("Expect '}' after unicode escape digits."));
+        }
+
+        if digits.is_empty() || digits.len() > 6 {
+            return Err(String::from(
+                "Unicode escape must have between 1 and 6 hex digits.",
+            ));
+        }
+
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("Invalid unicode code point '{digits}'."))
+    }
+
+    fn make_string(&self, typ: TokenType, line: usize, value: String) -> Token {
+        let length = self.current - self.start;
+        Token::new(typ, self.start, length, line, String::new(), value)
+    }
+
+    fn string_error(&self, line: usize, message: &str) -> Token {
+        let mut token = self.error_token(message);
+        token.line = line;
+        token
     }
 
     fn error_token(&self, message: &str) -> Token {
@@ -176,10 +501,7 @@ impl Scanner {
     }
 
     fn make_token(&self, typ: TokenType) -> Token {
-        let length = match typ {
-            TokenType::Str => self.current - self.start - 1,
-            _ => self.current - self.start,
-        };
+        let length = self.current - self.start;
         let lexeme = self.lexeme(self.start, length);
         Token::new(typ, self.start, length, self.line, String::new(), lexeme)
     }
@@ -207,10 +529,25 @@ impl Scanner {
     }
 
     fn peek_next(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
+        self.peek_at(1)
+    }
+
+    fn peek_at(&self, distance: usize) -> char {
+        match self.source.get(self.current + distance) {
+            Some(c) => *c,
+            None => '\0',
         }
-        self.source[self.current + 1]
+    }
+
+    fn peek_is(&self, expected: char) -> bool {
+        !self.is_at_end() && self.peek() == expected
+    }
+
+    fn after_assignable(&self) -> bool {
+        matches!(
+            self.previous,
+            TokenType::Identifier | TokenType::RightBracket
+        )
     }
 
     fn is_at_end(&self) -> bool {
@@ -220,13 +557,19 @@ impl Scanner {
     fn identifier_type(&self) -> TokenType {
         match self.source[self.start] {
             'a' => self.check_keyword("nd", TokenType::And),
-            'e' => self.check_keyword("lse", TokenType::Else),
-            'i' => self.check_keyword("f", TokenType::If),
+            'e' => self
+                .check_keyword("lse", TokenType::Else)
+                .or_keyword(|| self.check_keyword("xport", TokenType::Export)),
+            'i' => self
+                .check_keyword("f", TokenType::If)
+                .or_keyword(|| self.check_keyword("mport", TokenType::Import))
+                .or_keyword(|| self.check_keyword("n", TokenType::In)),
             'n' => self.check_keyword("il", TokenType::Nil),
             'o' => self.check_keyword("r", TokenType::Or),
             'p' => self.check_keyword("rint", TokenType::Print),
             'r' => self.check_keyword("eturn", TokenType::Return),
             'w' => self.check_keyword("hile", TokenType::While),
+            'y' => self.check_keyword("ield", TokenType::Yield),
             'd' => self.check_keyword("efault", TokenType::Default),
             'c' => {
                 if self.current - self.start == 1 {
@@ -234,7 +577,9 @@ impl Scanner {
                 }
 
                 match self.source[self.start + 1] {
-                    'a' => self.check_keyword("ase", TokenType::Case),
+                    'a' => self
+                        .check_keyword("ase", TokenType::Case)
+                        .or_keyword(|| self.check_keyword("atch", TokenType::Catch)),
                     'l' => self.check_keyword("lass", TokenType::Class),
                     _ => TokenType::Identifier,
                 }
@@ -286,8 +631,12 @@ impl Scanner {
                 }
 
                 match self.source[self.start + 1] {
-                    'h' => self.check_keyword("his", TokenType::This),
-                    'r' => self.check_keyword("rue", TokenType::True),
+                    'h' => self
+                        .check_keyword("his", TokenType::This)
+                        .or_keyword(|| self.check_keyword("hrow", TokenType::Throw)),
+                    'r' => self
+                        .check_keyword("rue", TokenType::True)
+                        .or_keyword(|| self.check_keyword("ry", TokenType::Try)),
                     _ => TokenType::Identifier,
                 }
             }
@@ -41,6 +41,8 @@ pub enum TokenType {
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
     Minus,
@@ -49,6 +51,11 @@ pub enum TokenType {
     Colon,
     Slash,
     Star,
+    Percent,
+    Ampersand,
+    Pipe,
+    Caret,
+    Question,
     // One or two character tokens
     Bang,
     BangEqual,
@@ -58,9 +65,26 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    LessLess,
+    GreaterGreater,
+    StarStar,
+    Tilde,
+    TildeSlash,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
+    PlusPlus,
+    MinusMinus,
+    QuestionQuestion,
+    Arrow,
+    DotDot,
+    DotDotEqual,
     // Literals
     Identifier,
     Str,
+    Interpolation,
     Number,
     // Keywords
     And,
@@ -83,11 +107,39 @@ pub enum TokenType {
     Case,
     Default,
     While,
+    Try,
+    Catch,
+    Throw,
+    Import,
+    Export,
+    In,
+    Yield,
     Error,
     Eof,
     None,
 }
 
+impl TokenType {
+    pub fn is_assignment(&self) -> bool {
+        matches!(
+            self,
+            Self::Equal
+                | Self::PlusEqual
+                | Self::MinusEqual
+                | Self::StarEqual
+                | Self::SlashEqual
+                | Self::PercentEqual
+        )
+    }
+
+    pub fn or_keyword(self, other: impl FnOnce() -> Self) -> Self {
+        match self {
+            Self::Identifier => other(),
+            typ => typ,
+        }
+    }
+}
+
 impl fmt::Display for TokenType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -95,6 +147,8 @@ impl fmt::Display for TokenType {
             Self::RightParen => write!(f, "RIGHT_PAREN"),
             Self::LeftBrace => write!(f, "LEFT_BRACE"),
             Self::RightBrace => write!(f, "RIGHT_BRACE"),
+            Self::LeftBracket => write!(f, "LEFT_BRACKET"),
+            Self::RightBracket => write!(f, "RIGHT_BRACKET"),
             Self::Comma => write!(f, "COMMA"),
             Self::Dot => write!(f, "DOT"),
             Self::Minus => write!(f, "MINUS"),
@@ -103,6 +157,11 @@ impl fmt::Display for TokenType {
             Self::Colon => write!(f, "COLON"),
             Self::Slash => write!(f, "SLASH"),
             Self::Star => write!(f, "STAR"),
+            Self::Percent => write!(f, "PERCENT"),
+            Self::Ampersand => write!(f, "AMPERSAND"),
+            Self::Pipe => write!(f, "PIPE"),
+            Self::Caret => write!(f, "CARET"),
+            Self::Question => write!(f, "QUESTION"),
             Self::Bang => write!(f, "BANG"),
             Self::BangEqual => write!(f, "BANG_EQUAL"),
             Self::Equal => write!(f, "EQUAL"),
@@ -111,8 +170,25 @@ impl fmt::Display for TokenType {
             Self::GreaterEqual => write!(f, "GREATER_EQUAL"),
             Self::Less => write!(f, "LESS"),
             Self::LessEqual => write!(f, "LESS_EQUAL"),
+            Self::LessLess => write!(f, "LESS_LESS"),
+            Self::GreaterGreater => write!(f, "GREATER_GREATER"),
+            Self::StarStar => write!(f, "STAR_STAR"),
+            Self::Tilde => write!(f, "TILDE"),
+            Self::TildeSlash => write!(f, "TILDE_SLASH"),
+            Self::PlusEqual => write!(f, "PLUS_EQUAL"),
+            Self::MinusEqual => write!(f, "MINUS_EQUAL"),
+            Self::StarEqual => write!(f, "STAR_EQUAL"),
+            Self::SlashEqual => write!(f, "SLASH_EQUAL"),
+            Self::PercentEqual => write!(f, "PERCENT_EQUAL"),
+            Self::PlusPlus => write!(f, "PLUS_PLUS"),
+            Self::MinusMinus => write!(f, "MINUS_MINUS"),
+            Self::QuestionQuestion => write!(f, "QUESTION_QUESTION"),
+            Self::Arrow => write!(f, "ARROW"),
+            Self::DotDot => write!(f, "DOT_DOT"),
+            Self::DotDotEqual => write!(f, "DOT_DOT_EQUAL"),
             Self::Identifier => write!(f, "IDENTIFIER"),
             Self::Str => write!(f, "STR"),
+            Self::Interpolation => write!(f, "INTERPOLATION"),
             Self::Number => write!(f, "NUMBER"),
             Self::And => write!(f, "AND"),
             Self::Class => write!(f, "CLASS"),
@@ -134,6 +210,13 @@ impl fmt::Display for TokenType {
             Self::Case => write!(f, "CASE"),
             Self::Default => write!(f, "DEFAULT"),
             Self::While => write!(f, "WHILE"),
+            Self::Try => write!(f, "TRY"),
+            Self::Catch => write!(f, "CATCH"),
+            Self::Throw => write!(f, "THROW"),
+            Self::Import => write!(f, "IMPORT"),
+            Self::Export => write!(f, "EXPORT"),
+            Self::In => write!(f, "IN"),
+            Self::Yield => write!(f, "YIELD"),
             Self::Error => write!(f, "ERROR"),
             Self::Eof => write!(f, "EOF"),
             Self::None => write!(f, "NONE"),
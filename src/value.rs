@@ -1,4 +1,9 @@
-use std::fmt;
+use std::{fmt, ptr};
+
+use crate::{arena::Arena, chunk::Chunk, fiber::Fiber, map::Map, vm::Vm};
+
+/// How deep printing and JSON follow nested lists and maps.
+pub const MAX_DEPTH: usize = 512;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Value {
@@ -10,17 +15,11 @@ pub enum Value {
 
 impl Value {
     pub fn is_number(&self) -> bool {
-        match self {
-            Self::Number(_) => true,
-            _ => false,
-        }
+        matches!(self, Self::Number(_))
     }
 
     pub fn is_falsey(&self) -> bool {
-        match self {
-            Self::Nil | Self::Bool(false) => true,
-            _ => false,
-        }
+        matches!(self, Self::Nil | Self::Bool(false))
     }
 
     pub fn as_obj(&self) -> usize {
@@ -60,12 +59,36 @@ impl Eq for Value {}
 pub enum Obj {
     Str(String),
     Ident(String),
+    Native(Native),
+    List(Vec<Value>),
+    Map(Map),
+    Function(Function),
+    Closure(Closure),
+    Upvalue(Upvalue),
+    Error(String),
+    Module(usize),
+    Iterator(IteratorState),
+    Range(Range),
+    Generator(Generator),
+    Fiber(Fiber),
 }
 
 impl Obj {
     pub fn name(&self) -> &String {
         match self {
             Self::Str(s) | Self::Ident(s) => s,
+            Self::Native(native) => &native.name,
+            Self::Function(function) => &function.name,
+            Self::List(_) => panic!("Obj of type 'List' has no name."),
+            Self::Map(_) => panic!("Obj of type 'Map' has no name."),
+            Self::Closure(_) => panic!("Obj of type 'Closure' has no name."),
+            Self::Upvalue(_) => panic!("Obj of type 'Upvalue' has no name."),
+            Self::Error(_) => panic!("Obj of type 'Error' has no name."),
+            Self::Module(_) => panic!("Obj of type 'Module' has no name."),
+            Self::Iterator(_) => panic!("Obj of type 'Iterator' has no name."),
+            Self::Range(_) => panic!("Obj of type 'Range' has no name."),
+            Self::Generator(_) => panic!("Obj of type 'Generator' has no name."),
+            Self::Fiber(_) => panic!("Obj of type 'Fiber' has no name."),
         }
     }
 }
@@ -74,6 +97,18 @@ impl fmt::Display for Obj {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::Str(s) | Self::Ident(s) => write!(f, "{s}"),
+            Self::Native(native) => write!(f, "<native fn {}>", native.name),
+            Self::List(list) => write!(f, "<list {}>", list.len()),
+            Self::Map(map) => write!(f, "<map {}>", map.len()),
+            Self::Function(function) => write!(f, "{function}"),
+            Self::Closure(_) => write!(f, "<closure>"),
+            Self::Upvalue(_) => write!(f, "<upvalue>"),
+            Self::Error(message) => write!(f, "{message}"),
+            Self::Module(_) => write!(f, "<module>"),
+            Self::Iterator(_) => write!(f, "<iterator>"),
+            Self::Range(range) => write!(f, "{range}"),
+            Self::Generator(_) => write!(f, "<generator>"),
+            Self::Fiber(_) => write!(f, "<fiber>"),
         }
     }
 }
@@ -83,9 +118,289 @@ impl PartialEq for Obj {
         match (self, other) {
             (Self::Str(s), Self::Str(o)) => s == o,
             (Self::Ident(s), Self::Ident(o)) => s == o,
+            (Self::Native(s), Self::Native(o)) => s.name == o.name,
+            // Containers are mutable, so they are only equal if they are the same object.
+            (Self::List(s), Self::List(o)) => ptr::eq(s, o),
+            (Self::Map(s), Self::Map(o)) => ptr::eq(s, o),
+            (Self::Function(s), Self::Function(o)) => ptr::eq(s, o),
+            (Self::Closure(s), Self::Closure(o)) => ptr::eq(s, o),
+            (Self::Upvalue(s), Self::Upvalue(o)) => ptr::eq(s, o),
+            (Self::Error(s), Self::Error(o)) => ptr::eq(s, o),
+            (Self::Module(s), Self::Module(o)) => s == o,
+            (Self::Iterator(s), Self::Iterator(o)) => ptr::eq(s, o),
+            (Self::Range(s), Self::Range(o)) => s == o,
+            (Self::Generator(s), Self::Generator(o)) => ptr::eq(s, o),
+            (Self::Fiber(s), Self::Fiber(o)) => ptr::eq(s, o),
             (_, _) => false,
         }
     }
 }
 
 impl Eq for Obj {}
+
+pub fn format_value(value: &Value, objects: &Arena<Obj>) -> String {
+    let mut text = String::new();
+    write_value(value, objects, &mut Vec::new(), &mut text);
+    text
+}
+
+fn write_value(value: &Value, objects: &Arena<Obj>, visiting: &mut Vec<usize>, text: &mut String) {
+    let index = match value {
+        Value::Obj(index) => *index,
+        _ => {
+            text.push_str(&value.to_string());
+            return;
+        }
+    };
+
+    let obj = objects.get(index);
+    if matches!(obj, Obj::List(_) | Obj::Map(_))
+        && (visiting.contains(&index) || visiting.len() == MAX_DEPTH)
+    {
+        text.push_str(match obj {
+            Obj::List(_) => "[...]",
+            _ => "{...}",
+        });
+        return;
+    }
+
+    match obj {
+        Obj::List(list) => {
+            visiting.push(index);
+            text.push('[');
+            for (position, element) in list.iter().enumerate() {
+                if position > 0 {
+                    text.push_str(", ");
+                }
+                write_value(element, objects, visiting, text);
+            }
+            text.push(']');
+            visiting.pop();
+        }
+        Obj::Map(map) => {
+            visiting.push(index);
+            text.push('{');
+            for (position, (key, value)) in map.entries().iter().enumerate() {
+                if position > 0 {
+                    text.push_str(", ");
+                }
+                write_value(key, objects, visiting, text);
+                text.push_str(": ");
+                write_value(value, objects, visiting, text);
+            }
+            text.push('}');
+            visiting.pop();
+        }
+        Obj::Closure(closure) => text.push_str(&objects.get(closure.function).to_string()),
+        obj => text.push_str(&obj.to_string()),
+    }
+}
+
+pub type NativeFn = fn(&mut Vm, &[Value]) -> Result<Value, String>;
+
+#[derive(Debug, Clone)]
+pub struct Native {
+    pub name: String,
+    pub arity: usize,
+    pub optional: usize,
+    pub function: NativeFn,
+}
+
+impl Native {
+    pub fn new(name: &str, arity: usize, function: NativeFn) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            optional: 0,
+            function,
+        }
+    }
+
+    pub fn optional(mut self, optional: usize) -> Self {
+        self.optional = optional;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub module: usize,
+    pub arity: usize,
+    pub is_generator: bool,
+    pub chunk: Chunk,
+    pub upvalues: Vec<UpvalueRef>,
+}
+
+impl Function {
+    pub fn new(name: String, module: usize) -> Self {
+        Self {
+            name,
+            module,
+            arity: 0,
+            is_generator: false,
+            chunk: Chunk::new(),
+            upvalues: Vec::new(),
+        }
+    }
+
+    pub fn is_script(&self) -> bool {
+        self.name.is_empty()
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_script() {
+            write!(f, "<script>")
+        } else {
+            write!(f, "<fn {}>", self.name)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpvalueRef {
+    pub is_local: bool,
+    pub index: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Closure {
+    pub function: usize,
+    pub upvalues: Vec<usize>,
+}
+
+impl Closure {
+    pub fn new(function: usize, upvalues: Vec<usize>) -> Self {
+        Self { function, upvalues }
+    }
+}
+
+/// A captured variable. While open, `owner` is the fiber or suspended
+/// generator whose stack holds the slot.
+#[derive(Debug, Clone, Copy)]
+pub enum Upvalue {
+    Open { owner: usize, slot: usize },
+    Closed(Value),
+}
+
+/// The position of a `for ... in` loop over a list, map or string. For a
+/// string, `position` is a byte offset.
+#[derive(Debug, Clone, Copy)]
+pub struct IteratorState {
+    pub target: Value,
+    pub position: usize,
+}
+
+impl IteratorState {
+    pub fn new(target: Value) -> Self {
+        Self {
+            target,
+            position: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: f64,
+    pub end: f64,
+    pub step: f64,
+    pub inclusive: bool,
+}
+
+impl Range {
+    pub fn new(start: f64, end: f64, step: f64, inclusive: bool) -> Result<Self, String> {
+        if step == 0.0 || step.is_nan() {
+            return Err(String::from("Range step cannot be zero."));
+        }
+
+        if !start.is_finite() || !end.is_finite() || !step.is_finite() {
+            return Err(String::from("Range bounds and step must be finite."));
+        }
+
+        // Past 2^53 steps the count can't be held exactly.
+        if (end - start) / step >= (1u64 << 53) as f64 {
+            return Err(String::from("Range is too large."));
+        }
+
+        Ok(Self {
+            start,
+            end,
+            step,
+            inclusive,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        let span = (self.end - self.start) / self.step;
+        if span < 0.0 {
+            return 0;
+        }
+
+        if self.inclusive {
+            span.floor() as usize + 1
+        } else {
+            span.ceil() as usize
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, index: usize) -> Option<f64> {
+        (index < self.len()).then_some(self.start + index as f64 * self.step)
+    }
+
+    pub fn contains(&self, n: f64) -> bool {
+        let offset = (n - self.start) / self.step;
+        offset >= 0.0 && offset.fract() == 0.0 && (offset as usize) < self.len()
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operator = if self.inclusive { "..=" } else { ".." };
+        write!(f, "{}{operator}{}", self.start, self.end)?;
+        if self.step != 1.0 {
+            write!(f, " step {}", self.step)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GeneratorState {
+    Suspended,
+    Running,
+    Done,
+}
+
+/// A paused generator call and its saved stack window.
+#[derive(Debug, Clone)]
+pub struct Generator {
+    pub closure: usize,
+    pub function: usize,
+    pub ip: usize,
+    pub stack: Vec<Value>,
+    // The catch ip and stack offset of each `try` block open at the yield.
+    pub handlers: Vec<(usize, usize)>,
+    pub upvalues: Vec<usize>,
+    pub state: GeneratorState,
+}
+
+impl Generator {
+    pub fn new(closure: usize, function: usize, stack: Vec<Value>) -> Self {
+        Self {
+            closure,
+            function,
+            ip: 0,
+            stack,
+            handlers: Vec::new(),
+            upvalues: Vec::new(),
+            state: GeneratorState::Suspended,
+        }
+    }
+}
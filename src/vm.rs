@@ -1,178 +1,571 @@
 use std::{
     collections::HashMap,
-    env, fs,
+    f64, fs,
     io::{self, BufRead, Write},
+    mem,
+    path::PathBuf,
 };
 
-use crate::{arena::Arena, chunk::*, compiler::*, value::*};
+use crate::{
+    arena::Arena,
+    chunk::*,
+    clock::Clock,
+    compiler::*,
+    config::VmConfig,
+    fiber::*,
+    map::{Map, MapKey},
+    module::Module,
+    native,
+    rng::Rng,
+    value::*,
+};
+
+const FRAMES_MAX: usize = 256;
+const MAIN_MODULE: usize = 0;
+
+/// A context switch asked for by a fiber native, made once it returns.
+#[derive(Debug, Clone, Copy)]
+enum FiberSwitch {
+    Resume(usize, Value),
+    Suspend(Value),
+}
 
-#[derive(Debug, Clone)]
 pub struct Vm {
-    ip: usize,
+    frames: Vec<CallFrame>,
     stack: Vec<Value>,
+    open_upvalues: Vec<usize>,
+    handlers: Vec<Handler>,
+    // The fiber whose context the fields above hold.
+    fiber: usize,
+    main_fiber: usize,
+    fiber_switch: Option<FiberSwitch>,
+    // An uncaught throw, passed on as-is to the resuming fiber.
+    thrown: Option<Value>,
     objects: Arena<Obj>,
-    globals: HashMap<String, Value>,
+    builtins: HashMap<String, Value>,
+    modules: Vec<Module>,
+    module_paths: HashMap<PathBuf, usize>,
+    args: Vec<String>,
+    rng: Rng,
+    config: VmConfig,
 }
 
 impl Vm {
     pub fn new() -> Self {
-        Self {
-            ip: 0,
+        Self::with_config(VmConfig::default())
+    }
+
+    pub fn with_config(config: VmConfig) -> Self {
+        let seed = config
+            .seed
+            .unwrap_or_else(|| (config.clock.now() * 1e9) as u64);
+        let mut vm = Self {
+            frames: Vec::new(),
             stack: Vec::new(),
+            open_upvalues: Vec::new(),
+            handlers: Vec::new(),
+            fiber: 0,
+            main_fiber: 0,
+            fiber_switch: None,
+            thrown: None,
             objects: Arena::new(),
-            globals: HashMap::new(),
+            builtins: HashMap::new(),
+            modules: vec![Module::new(None)],
+            module_paths: HashMap::new(),
+            args: Vec::new(),
+            rng: Rng::new(seed),
+            config,
+        };
+
+        vm.main_fiber = vm.alloc(Obj::Fiber(Fiber::main())).as_obj();
+        vm.fiber = vm.main_fiber;
+
+        vm.define_native("argc", 0, native::argc);
+        vm.define_native("argv", 1, native::argv);
+        vm.define_native("len", 1, native::len);
+        vm.define_native("push", 2, native::push);
+        vm.define_native("pop", 1, native::pop);
+        vm.define_native("has", 2, native::has);
+        vm.define_native("remove", 2, native::remove);
+        vm.define_native("keys", 1, native::keys);
+        vm.define_native("values", 1, native::values);
+        vm.define_native("entries", 1, native::entries);
+        vm.define_native("error", 1, native::error);
+        vm.define_native("fiber", 1, native::fiber);
+        vm.define_builtin(Native::new("resume", 1, native::resume).optional(1));
+        vm.define_native("suspend", 1, native::suspend);
+        vm.define_native("isDone", 1, native::is_done);
+
+        vm.define_module(
+            "math",
+            vec![
+                Native::new("sqrt", 1, native::math::sqrt),
+                Native::new("pow", 2, native::math::pow),
+                Native::new("abs", 1, native::math::abs),
+                Native::new("floor", 1, native::math::floor),
+                Native::new("ceil", 1, native::math::ceil),
+                Native::new("round", 1, native::math::round),
+                Native::new("sin", 1, native::math::sin),
+                Native::new("cos", 1, native::math::cos),
+                Native::new("tan", 1, native::math::tan),
+                Native::new("asin", 1, native::math::asin),
+                Native::new("acos", 1, native::math::acos),
+                Native::new("atan", 1, native::math::atan),
+                Native::new("atan2", 2, native::math::atan2),
+                Native::new("log", 1, native::math::log),
+                Native::new("log2", 1, native::math::log2),
+                Native::new("log10", 1, native::math::log10),
+                Native::new("exp", 1, native::math::exp),
+                Native::new("min", 2, native::math::min),
+                Native::new("max", 2, native::math::max),
+                Native::new("isnan", 1, native::math::isnan),
+                Native::new("isinf", 1, native::math::isinf),
+            ],
+            &[
+                ("pi", Value::Number(f64::consts::PI)),
+                ("e", Value::Number(f64::consts::E)),
+                ("inf", Value::Number(f64::INFINITY)),
+                ("nan", Value::Number(f64::NAN)),
+            ],
+        );
+        vm.define_module(
+            "string",
+            vec![
+                Native::new("length", 1, native::string::length),
+                Native::new("slice", 3, native::string::slice),
+                Native::new("find", 2, native::string::find),
+                Native::new("replace", 3, native::string::replace),
+                Native::new("split", 2, native::string::split),
+                Native::new("join", 2, native::string::join),
+                Native::new("trim", 1, native::string::trim),
+                Native::new("upper", 1, native::string::upper),
+                Native::new("lower", 1, native::string::lower),
+                Native::new("startsWith", 2, native::string::starts_with),
+                Native::new("endsWith", 2, native::string::ends_with),
+                Native::new("code", 2, native::string::code),
+                Native::new("fromCode", 1, native::string::from_code),
+                Native::new("repeat", 2, native::string::repeat),
+                Native::new("toNumber", 1, native::string::to_number),
+            ],
+            &[],
+        );
+
+        vm.define_module(
+            "json",
+            vec![
+                Native::new("parse", 1, native::json::parse),
+                Native::new("stringify", 1, native::json::stringify).optional(1),
+            ],
+            &[],
+        );
+        vm.define_module(
+            "random",
+            vec![
+                Native::new("seed", 1, native::random::seed),
+                Native::new("float", 0, native::random::float),
+                Native::new("int", 2, native::random::int),
+                Native::new("choice", 1, native::random::choice),
+                Native::new("shuffle", 1, native::random::shuffle),
+            ],
+            &[],
+        );
+
+        vm.define_module(
+            "time",
+            vec![
+                Native::new("now", 0, native::time::now),
+                Native::new("monotonic", 0, native::time::monotonic),
+                Native::new("sleep", 1, native::time::sleep),
+                Native::new("format", 1, native::time::format),
+                Native::new("parse", 1, native::time::parse),
+            ],
+            &[],
+        );
+
+        if vm.config.io {
+            vm.define_module(
+                "io",
+                vec![
+                    Native::new("readLine", 0, native::io::read_line),
+                    Native::new("readFile", 1, native::io::read_file),
+                    Native::new("writeFile", 2, native::io::write_file),
+                    Native::new("appendFile", 2, native::io::append_file),
+                    Native::new("exists", 1, native::io::exists),
+                    Native::new("listDir", 1, native::io::list_dir),
+                ],
+                &[],
+            );
         }
+        vm
     }
 
     pub fn repl(&mut self) -> Interpret {
-        println!("=== Welcome to blox v2.0");
-        println!("=== Enter 'q' or 'Q' to quit");
-        print!("> ");
-        io::stdout().flush().expect("Error flushing stdout.");
+        self.write_output("=== Welcome to blox v2.0\n=== Enter 'q' or 'Q' to quit\n> ");
         for line in io::stdin().lock().lines() {
             let input = line.unwrap_or_else(|e| {
-                eprintln!("Error reading input {e}");
+                self.diagnostic(&format!("Error reading input {e}"));
                 String::from("")
             });
 
             if input.is_empty() {
-                print!("> ");
-                io::stdout().flush().expect("Error flushing stdout.");
+                self.write_output("> ");
                 continue;
             }
 
             if input.to_lowercase().trim() == "q" {
-                println!("=== Goodbye!");
+                self.write_output("=== Goodbye!\n");
                 return Interpret::Ok;
             }
 
             self.interpret(input);
             self.reset_stack();
-            print!("> ");
-            io::stdout().flush().expect("Error flushing stdout.");
+            self.write_output("> ");
         }
 
         Interpret::Ok
     }
 
-    pub fn run_file(&mut self, path: &str) -> Interpret {
+    pub fn run_file(&mut self, path: &str, args: &[String]) -> Interpret {
+        self.args = Vec::with_capacity(args.len() + 1);
+        self.args.push(path.to_string());
+        self.args.extend_from_slice(args);
+
+        if let Ok(canonical) = fs::canonicalize(path) {
+            self.modules[MAIN_MODULE] = Module::new(Some(canonical.clone()));
+            self.module_paths.insert(canonical, MAIN_MODULE);
+        }
+
         match fs::read_to_string(path) {
             Ok(source) => self.interpret(source),
             Err(e) => {
-                eprintln!("Failed to open file at {path}: {e}");
+                self.diagnostic(&format!("Failed to open file at {path}: {e}"));
                 Interpret::RuntimeError
             }
         }
     }
 
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    pub fn alloc(&mut self, obj: Obj) -> Value {
+        self.objects.push(obj);
+        Value::Obj(self.objects.len() - 1)
+    }
+
+    pub fn object(&self, value: Value) -> Option<&Obj> {
+        match value {
+            Value::Obj(index) => Some(self.objects.get(index)),
+            _ => None,
+        }
+    }
+
+    pub fn object_mut(&mut self, value: Value) -> Option<&mut Obj> {
+        match value {
+            Value::Obj(index) => Some(self.objects.get_mut(index)),
+            _ => None,
+        }
+    }
+
+    pub fn format(&self, value: Value) -> String {
+        format_value(&value, &self.objects)
+    }
+
+    pub(crate) fn clock(&self) -> &dyn Clock {
+        self.config.clock.as_ref()
+    }
+
+    pub(crate) fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    pub(crate) fn reseed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
+    }
+
+    pub(crate) fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        let read = match &mut self.config.input {
+            Some(input) => input.read_line(&mut line)?,
+            None => io::stdin().read_line(&mut line)?,
+        };
+        if read == 0 {
+            return Ok(None);
+        }
+
+        let trimmed = line.trim_end_matches(['\n', '\r']).len();
+        line.truncate(trimmed);
+        Ok(Some(line))
+    }
+
+    pub(crate) fn map_key(&self, value: Value) -> Result<MapKey, String> {
+        MapKey::from_value(&value, &self.objects)
+    }
+
+    fn define_native(&mut self, name: &str, arity: usize, function: NativeFn) {
+        self.define_builtin(Native::new(name, arity, function));
+    }
+
+    fn define_builtin(&mut self, native: Native) {
+        let name = native.name.clone();
+        let native = self.alloc(Obj::Native(native));
+        self.builtins.insert(name, native);
+    }
+
+    fn define_module(&mut self, name: &str, natives: Vec<Native>, constants: &[(&str, Value)]) {
+        let mut globals: HashMap<String, Value> = constants
+            .iter()
+            .map(|(name, value)| (name.to_string(), *value))
+            .collect();
+        for native in natives {
+            let name = native.name.clone();
+            let value = self.alloc(Obj::Native(native));
+            globals.insert(name, value);
+        }
+
+        let module = self.modules.len();
+        self.modules.push(Module::builtin(name, globals));
+        let value = self.alloc(Obj::Module(module));
+        self.builtins.insert(name.to_string(), value);
+    }
+
     fn interpret(&mut self, source: String) -> Interpret {
-        let compiler = Compiler::new(source, &mut self.objects);
-        let chunk = match compiler.compile() {
-            Ok(compiled) => compiled.chunk,
-            Err(()) => return Interpret::CompileError,
+        if !self.compile_module(source, MAIN_MODULE) {
+            return Interpret::CompileError;
+        }
+
+        if let Err(e) = self.call_value(0) {
+            return self.runtime_error(&e, Vec::new());
+        }
+
+        self.run()
+    }
+
+    fn compile_module(&mut self, source: String, module: usize) -> bool {
+        let compiler = Compiler::new(source, &mut self.objects, module);
+        let function = match compiler.compile() {
+            Ok(function) => function,
+            Err(errors) => {
+                for error in errors {
+                    self.diagnostic(&error);
+                }
+                return false;
+            }
         };
 
-        self.run(chunk)
+        if self.config.print_code {
+            disassemble_function(&function, &self.objects, &mut self.config.trace_output)
+                .expect("Error writing code listing.");
+        }
+
+        let function = self.alloc(Obj::Function(function)).as_obj();
+        let closure = self.alloc(Obj::Closure(Closure::new(function, Vec::new())));
+        self.push(closure);
+        true
+    }
+
+    fn import(&mut self, path: &str) -> Result<(), String> {
+        let directory = self.modules[self.current_module()].directory();
+        let resolved = fs::canonicalize(directory.join(path))
+            .map_err(|e| format!("Could not find module '{path}': {e}."))?;
+
+        if let Some(&module) = self.module_paths.get(&resolved) {
+            if !self.modules[module].loaded {
+                return Err(format!(
+                    "Import cycle: module '{path}' is already being imported."
+                ));
+            }
+
+            let value = self.alloc(Obj::Module(module));
+            self.push(value);
+            return Ok(());
+        }
+
+        let source = fs::read_to_string(&resolved)
+            .map_err(|e| format!("Could not read module '{path}': {e}."))?;
+        let module = self.modules.len();
+        self.modules.push(Module::new(Some(resolved.clone())));
+        if !self.compile_module(source, module) {
+            return Err(format!("Could not compile module '{path}'."));
+        }
+
+        self.module_paths.insert(resolved, module);
+        self.call_value(0)
+    }
+
+    fn current_module(&self) -> usize {
+        self.function(self.frame().function).module
+    }
+
+    fn run(&mut self) -> Interpret {
+        loop {
+            let Err(message) = self.execute() else {
+                return Interpret::Ok;
+            };
+
+            // An uncaught error ends a fiber and moves on to its resumer.
+            let error = match self.thrown.take() {
+                Some(value) => value,
+                None => self.alloc(Obj::Error(message.clone())),
+            };
+            let mut trace = Vec::new();
+            while !self.unwind(error) {
+                if self.fiber == self.main_fiber {
+                    return self.runtime_error(&message, trace);
+                }
+
+                trace.extend(self.stack_trace());
+                self.close_upvalues(0);
+                let abandoned = mem::take(&mut self.frames);
+                self.abandon_frames(abandoned);
+                self.finish_fiber(Value::Nil);
+            }
+        }
     }
 
-    fn run(&mut self, chunk: Chunk) -> Interpret {
+    fn execute(&mut self) -> Result<(), String> {
         loop {
-            let ip = self.ip;
-            let op = chunk.read_op(ip).to_owned();
+            let frame = self.frame();
+            let ip = frame.ip;
+            let op = *self.chunk(frame.function).read_op(ip);
 
-            if env::var("DEBUG_TRACE_EXECUTION").is_ok_and(|var| var == "1") {
-                self.stack_trace();
-                chunk.disassemble_instruction(ip, &op, &self.objects);
-                println!()
+            if self.config.trace_execution {
+                self.trace_instruction(ip, &op);
             }
 
-            self.ip += 1;
+            self.frame_mut().ip += 1;
 
             match op {
-                Op::Constant(index) => self.push(chunk.read_constant(index).to_owned()),
+                Op::Constant(index) => self.push(self.read_constant(index)),
                 Op::Nil => self.push(Value::Nil),
                 Op::True => self.push(Value::Bool(true)),
                 Op::False => self.push(Value::Bool(false)),
                 Op::Pop => _ = self.pop(),
+                Op::Dup => self.push(*self.peek(0)),
+                Op::DupPair => {
+                    let (second, first) = (*self.peek(1), *self.peek(0));
+                    self.push(second);
+                    self.push(first);
+                }
+                Op::Rotate(depth) => {
+                    let value = self.pop();
+                    self.stack.insert(self.stack.len() - depth, value);
+                }
                 Op::DefineGlobal(index) => {
                     let value = self.pop();
-                    let identifier = self.objects.get(chunk.read_constant(index).as_obj());
-                    self.globals.insert(identifier.name().clone(), value);
+                    let identifier = self.objects.get(self.read_constant(index).as_obj());
+                    let module = self.current_module();
+                    self.modules[module]
+                        .globals
+                        .insert(identifier.name().clone(), value);
                 }
                 Op::GetGlobal(index) => {
-                    let identifier = self.objects.get(chunk.read_constant(index).as_obj());
+                    let identifier = self.objects.get(self.read_constant(index).as_obj());
                     let lexeme = identifier.name();
-                    match self.globals.get(lexeme) {
+                    let globals = &self.modules[self.current_module()].globals;
+                    match globals.get(lexeme).or_else(|| self.builtins.get(lexeme)) {
                         Some(value) => self.push(value.to_owned()),
-                        None => {
-                            let message = format!("Undefined variable '{lexeme}'");
-                            return self.runtime_error(&message, &chunk);
-                        }
+                        None => return Err(format!("Undefined variable '{lexeme}'")),
                     }
                 }
                 Op::SetGlobal(index) => {
-                    let identifier = self.objects.get(chunk.read_constant(index).as_obj());
+                    let identifier = self.objects.get(self.read_constant(index).as_obj());
                     let lexeme = identifier.name();
-                    if !self.globals.contains_key(lexeme) {
-                        let message = format!("Undefined variable '{lexeme}'");
-                        return self.runtime_error(&message, &chunk);
+                    let value = *self.peek(0);
+                    let module = self.current_module();
+                    match self.modules[module].globals.get_mut(lexeme) {
+                        Some(global) => *global = value,
+                        None => return Err(format!("Undefined variable '{lexeme}'")),
                     }
-
-                    self.globals.insert(lexeme.clone(), self.peek(0).to_owned());
                 }
-                Op::GetLocal(index) => self.push(self.local_at(index).clone()),
-                Op::SetLocal(index) => *self.local_at_mut(index) = self.peek(0).clone(),
-                Op::Equal => {
-                    let result = match (self.pop(), self.pop()) {
-                        (Value::Bool(second), Value::Bool(first)) => first == second,
-                        (Value::Number(second), Value::Number(first)) => first == second,
-                        (Value::Nil, Value::Nil) => true,
-                        (Value::Obj(second_i), Value::Obj(first_i)) => {
-                            let first = self.objects.get(first_i);
-                            let second = self.objects.get(second_i);
-                            first == second
-                        }
-                        _ => false,
+                Op::GetLocal(index) => self.push(*self.local_at(index)),
+                Op::GetUpvalue(index) => {
+                    let upvalue = self.closure_upvalue(self.frame().closure, index);
+                    let value = match self.objects.get(upvalue) {
+                        Obj::Upvalue(Upvalue::Open { owner, slot }) => *self
+                            .owner_stack(*owner)
+                            .get(*slot)
+                            .expect("Dangling upvalue."),
+                        Obj::Upvalue(Upvalue::Closed(value)) => *value,
+                        obj => panic!("Expected an upvalue but found {obj}."),
                     };
-                    self.push(Value::Bool(result));
+                    self.push(value);
                 }
-                Op::Greater => {
-                    if let Err(e) = self.binary_op(|a, b| Value::Bool(a > b)) {
-                        return self.runtime_error(&e, &chunk);
+                Op::SetUpvalue(index) => {
+                    let upvalue = self.closure_upvalue(self.frame().closure, index);
+                    let value = *self.peek(0);
+                    match self.objects.get_mut(upvalue) {
+                        Obj::Upvalue(Upvalue::Open { owner, slot }) => {
+                            let (owner, slot) = (*owner, *slot);
+                            self.owner_stack_mut(owner)[slot] = value;
+                        }
+                        Obj::Upvalue(Upvalue::Closed(closed)) => *closed = value,
+                        obj => panic!("Expected an upvalue but found {obj}."),
                     }
                 }
+                Op::SetLocal(index) => *self.local_at_mut(index) = *self.peek(0),
+                Op::Equal => {
+                    let (second, first) = (self.pop(), self.pop());
+                    self.push(Value::Bool(self.values_equal(first, second)));
+                }
+                Op::Greater => {
+                    self.binary_op(|a, b| Value::Bool(a > b))?;
+                }
                 Op::Less => {
-                    if let Err(e) = self.binary_op(|a, b| Value::Bool(a < b)) {
-                        return self.runtime_error(&e, &chunk);
-                    }
+                    self.binary_op(|a, b| Value::Bool(a < b))?;
                 }
                 Op::Add => {
-                    if let Err(e) = self.add() {
-                        return self.runtime_error(&e, &chunk);
-                    }
+                    self.add()?;
                 }
                 Op::Subtract => {
-                    if let Err(e) = self.binary_op(|a, b| Value::Number(a - b)) {
-                        return self.runtime_error(&e, &chunk);
-                    }
+                    self.binary_op(|a, b| Value::Number(a - b))?;
                 }
                 Op::Multiply => {
-                    if let Err(e) = self.binary_op(|a, b| Value::Number(a * b)) {
-                        return self.runtime_error(&e, &chunk);
-                    }
+                    self.binary_op(|a, b| Value::Number(a * b))?;
                 }
                 Op::Divide => {
-                    if let Err(e) = self.binary_op(|a, b| Value::Number(a / b)) {
-                        return self.runtime_error(&e, &chunk);
-                    }
+                    self.binary_op(|a, b| Value::Number(a / b))?;
+                }
+                Op::FloorDivide => {
+                    self.binary_op(|a, b| Value::Number((a / b).floor()))?;
                 }
+                Op::Modulo => {
+                    // Floored, so the result takes the sign of the divisor.
+                    let modulo = |a: f64, b: f64| Value::Number(a - b * (a / b).floor());
+                    self.binary_op(modulo)?;
+                }
+                Op::Power => {
+                    self.binary_op(|a, b| Value::Number(a.powf(b)))?;
+                }
+                Op::BitAnd => {
+                    self.integer_op(|a, b| Ok(a & b))?;
+                }
+                Op::BitOr => {
+                    self.integer_op(|a, b| Ok(a | b))?;
+                }
+                Op::BitXor => {
+                    self.integer_op(|a, b| Ok(a ^ b))?;
+                }
+                Op::ShiftLeft => {
+                    self.integer_op(|a, b| Ok(a << shift_amount(b)?))?;
+                }
+                Op::ShiftRight => {
+                    self.integer_op(|a, b| Ok(a >> shift_amount(b)?))?;
+                }
+                Op::BitNot => match as_integer(*self.peek(0)) {
+                    Some(n) => {
+                        self.pop();
+                        self.push(Value::Number(!n as f64));
+                    }
+                    None => return Err(String::from("Operand must be an integer.")),
+                },
                 Op::Not => {
                     let value = self.pop();
                     self.push(Value::Bool(value.is_falsey()));
                 }
                 Op::Negate => {
                     if !self.peek(0).is_number() {
-                        return self.runtime_error("Cannot negate a non-number.", &chunk);
+                        return Err(String::from("Cannot negate a non-number."));
                     }
 
                     if let Value::Number(n) = self.pop() {
@@ -181,27 +574,725 @@ impl Vm {
                 }
                 Op::Print => {
                     let value = self.pop();
-                    match value {
-                        Value::Obj(index) => println!("{}", self.objects.get(index)),
-                        _ => println!("{value}"),
-                    }
+                    let text = format_value(&value, &self.objects);
+                    writeln!(self.config.output, "{text}").expect("Error writing program output.");
                 }
                 Op::JumpIfFalse(index) => {
                     if self.peek(0).is_falsey() {
-                        self.ip = index;
+                        self.frame_mut().ip = index;
+                    }
+                }
+                Op::JumpIfNotNil(index) => {
+                    if !matches!(self.peek(0), Value::Nil) {
+                        self.frame_mut().ip = index;
+                    }
+                }
+                Op::Jump(index) => self.frame_mut().ip = index,
+                Op::Loop(index) => self.frame_mut().ip = index,
+                Op::Call(arg_count) => {
+                    self.call_value(arg_count)?;
+                }
+                Op::Closure(index) => {
+                    let function = self.read_constant(index).as_obj();
+                    let upvalue_refs = match self.objects.get(function) {
+                        Obj::Function(function) => function.upvalues.clone(),
+                        obj => panic!("Expected a function but found {obj}."),
+                    };
+
+                    let frame = self.frame();
+                    let mut upvalues = Vec::with_capacity(upvalue_refs.len());
+                    for upvalue in upvalue_refs {
+                        let captured = if upvalue.is_local {
+                            self.capture_upvalue(frame.slot_base + upvalue.index)
+                        } else {
+                            self.closure_upvalue(frame.closure, upvalue.index)
+                        };
+                        upvalues.push(captured);
+                    }
+
+                    let closure = self.alloc(Obj::Closure(Closure::new(function, upvalues)));
+                    self.push(closure);
+                }
+                Op::CloseUpvalue => {
+                    self.close_upvalues(self.stack_top());
+                    self.pop();
+                }
+                Op::BuildList(element_count) => {
+                    let elements = self.stack.split_off(self.stack.len() - element_count);
+                    let list = self.alloc(Obj::List(elements));
+                    self.push(list);
+                }
+                Op::BuildMap(entry_count) => {
+                    let entries = self.stack.split_off(self.stack.len() - entry_count * 2);
+                    let mut map = Map::new();
+                    for entry in entries.chunks(2) {
+                        let key = self.map_key(entry[0])?;
+                        map.insert(key, entry[0], entry[1]);
+                    }
+
+                    let map = self.alloc(Obj::Map(map));
+                    self.push(map);
+                }
+                Op::BuildString(part_count) => {
+                    let parts = self.stack.split_off(self.stack.len() - part_count);
+                    let string = parts
+                        .iter()
+                        .map(|part| format_value(part, &self.objects))
+                        .collect::<String>();
+                    let string = self.alloc(Obj::Str(string));
+                    self.push(string);
+                }
+                Op::GetIndex => {
+                    let index = self.pop();
+                    let target = self.pop();
+                    let value = match self.object(index) {
+                        Some(Obj::Range(range)) => self.slice(target, *range)?,
+                        _ => self.get_index(target, index)?,
+                    };
+                    self.push(value);
+                }
+                Op::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let target = self.pop();
+                    self.set_index(target, index, value)?;
+                    self.push(value);
+                }
+                Op::PushHandler(catch_ip) => self.handlers.push(Handler {
+                    frame_count: self.frames.len(),
+                    stack_len: self.stack.len(),
+                    catch_ip,
+                }),
+                Op::PopHandler => _ = self.handlers.pop(),
+                Op::Throw => {
+                    let value = self.pop();
+                    if !self.unwind(value) {
+                        self.thrown = Some(value);
+                        return Err(match self.object(value) {
+                            Some(Obj::Error(message)) => message.clone(),
+                            _ => format!("Uncaught exception: {}", self.format(value)),
+                        });
                     }
                 }
-                Op::Jump(index) => self.ip = index,
-                Op::Loop(index) => self.ip = index,
-                Op::Return => return Interpret::Ok,
+                Op::Import(index) => {
+                    let path = self.read_constant(index);
+                    let path = format_value(&path, &self.objects);
+                    self.import(&path)?;
+                }
+                Op::Export(index) => {
+                    let name = self.objects.get(self.read_constant(index).as_obj());
+                    let name = name.name().clone();
+                    let module = self.current_module();
+                    self.modules[module].exports.insert(name);
+                }
+                Op::GetProperty(index) => {
+                    let target = self.pop();
+                    let name = self.objects.get(self.read_constant(index).as_obj());
+                    let value = match self.object(target) {
+                        Some(Obj::Module(module)) => self.modules[*module].export(name.name())?,
+                        _ => return Err(String::from("Only modules have properties.")),
+                    };
+                    self.push(value);
+                }
+                Op::Iterate => {
+                    let target = self.pop();
+                    let iterator = match self.object(target) {
+                        Some(Obj::List(_) | Obj::Map(_) | Obj::Str(_) | Obj::Range(_)) => {
+                            self.alloc(Obj::Iterator(IteratorState::new(target)))
+                        }
+                        Some(Obj::Closure(closure))
+                            if self.function(closure.function).is_generator =>
+                        {
+                            self.push(target);
+                            self.call_value(0)?;
+                            self.pop()
+                        }
+                        // Functions iterate by being called until they return nil.
+                        Some(Obj::Closure(_) | Obj::Native(_) | Obj::Generator(_)) => target,
+                        _ => {
+                            return Err(String::from(
+                                "Can only iterate over lists, maps, strings, ranges and functions.",
+                            ));
+                        }
+                    };
+                    self.push(iterator);
+                }
+                Op::IterNext => {
+                    let iterator = self.pop();
+                    if let Some(Obj::Iterator(_)) = self.object(iterator) {
+                        let next = self.iterator_next(iterator);
+                        self.push(next.unwrap_or(Value::Nil));
+                        self.push(Value::Bool(next.is_some()));
+                    } else {
+                        self.push(iterator);
+                        let frame_count = self.frames.len();
+                        self.call_value(0)?;
+                        if self.frames.len() > frame_count {
+                            self.frame_mut().iterator = true;
+                        } else {
+                            let more = !matches!(self.peek(0), Value::Nil);
+                            self.push(Value::Bool(more));
+                        }
+                    }
+                }
+                Op::Range | Op::RangeInclusive => {
+                    let step = match self.pop() {
+                        Value::Nil => 1.0,
+                        Value::Number(step) => step,
+                        _ => return Err(String::from("Range step must be a number.")),
+                    };
+                    let (Value::Number(end), Value::Number(start)) = (self.pop(), self.pop())
+                    else {
+                        return Err(String::from("Range bounds must be numbers."));
+                    };
+
+                    let range = Range::new(start, end, step, matches!(op, Op::RangeInclusive))?;
+                    let range = self.alloc(Obj::Range(range));
+                    self.push(range);
+                }
+                Op::In => {
+                    let container = self.pop();
+                    let item = self.pop();
+                    let found = self.contains(container, item)?;
+                    self.push(Value::Bool(found));
+                }
+                Op::Return => {
+                    let result = self.pop();
+                    let frame = self.frames.pop().expect("Returning with no call frame.");
+                    self.close_upvalues(frame.slot_base);
+                    self.stack.truncate(frame.slot_base);
+                    let frame_count = self.frames.len();
+                    self.handlers
+                        .retain(|handler| handler.frame_count <= frame_count);
+                    if self.frames.is_empty() {
+                        if self.fiber == self.main_fiber {
+                            return Ok(());
+                        }
+
+                        self.finish_fiber(result);
+                        continue;
+                    }
+
+                    // A module's top-level code returns the module.
+                    let function = self.function(frame.function);
+                    let result = if function.is_script() {
+                        let module = function.module;
+                        self.modules[module].loaded = true;
+                        self.alloc(Obj::Module(module))
+                    } else {
+                        result
+                    };
+                    self.push(result);
+
+                    let finished_generator = frame.generator.is_some();
+                    self.finish_generators(vec![frame]);
+                    if frame.iterator {
+                        let more = !finished_generator && !matches!(result, Value::Nil);
+                        self.push(Value::Bool(more));
+                    }
+                }
+                Op::Yield => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().expect("Yielding with no call frame.");
+                    let generator = frame.generator.expect("Yield outside a generator.");
+
+                    // Upvalues over the window move with it.
+                    let upvalues = self.take_upvalues(frame.slot_base);
+                    self.retarget_upvalues(&upvalues, generator, frame.slot_base, 0);
+                    let window = self.stack.split_off(frame.slot_base);
+                    let frame_count = self.frames.len();
+                    let first_own = self
+                        .handlers
+                        .iter()
+                        .position(|handler| handler.frame_count > frame_count)
+                        .unwrap_or(self.handlers.len());
+                    let handlers = self
+                        .handlers
+                        .split_off(first_own)
+                        .into_iter()
+                        .map(|handler| (handler.catch_ip, handler.stack_len - frame.slot_base))
+                        .collect();
+
+                    if let Obj::Generator(state) = self.objects.get_mut(generator) {
+                        state.ip = frame.ip;
+                        state.stack = window;
+                        state.handlers = handlers;
+                        state.upvalues = upvalues;
+                        state.state = GeneratorState::Suspended;
+                    }
+
+                    self.push(value);
+                    if frame.iterator {
+                        self.push(Value::Bool(true));
+                    }
+                }
+            }
+        }
+    }
+
+    fn call_value(&mut self, arg_count: usize) -> Result<(), String> {
+        let callee = *self.peek(arg_count);
+        match self.object(callee) {
+            Some(Obj::Closure(closure)) => {
+                let function = closure.function;
+                self.call(callee.as_obj(), function, arg_count)
+            }
+            Some(Obj::Native(native)) => {
+                let native = native.clone();
+                self.call_native(native, arg_count)
+            }
+            Some(Obj::Generator(_)) => self.resume(callee.as_obj(), arg_count),
+            _ => Err(String::from("Can only call functions.")),
+        }
+    }
+
+    fn call(&mut self, closure: usize, function: usize, arg_count: usize) -> Result<(), String> {
+        let arity = self.function(function).arity;
+        if arg_count != arity {
+            return Err(format!("Expected {arity} arguments but got {arg_count}."));
+        }
+
+        if self.function(function).is_generator {
+            let window = self.stack.split_off(self.stack.len() - arg_count - 1);
+            let generator = Generator::new(closure, function, window);
+            let generator = self.alloc(Obj::Generator(generator));
+            self.push(generator);
+            return Ok(());
+        }
+
+        if self.frames.len() == FRAMES_MAX {
+            return Err(String::from("Stack overflow."));
+        }
+
+        self.frames.push(CallFrame {
+            closure,
+            function,
+            ip: 0,
+            slot_base: self.stack.len() - arg_count - 1,
+            iterator: false,
+            generator: None,
+        });
+        Ok(())
+    }
+
+    fn resume(&mut self, generator: usize, arg_count: usize) -> Result<(), String> {
+        if arg_count != 0 {
+            return Err(format!("Expected 0 arguments but got {arg_count}."));
+        }
+
+        if self.frames.len() == FRAMES_MAX {
+            return Err(String::from("Stack overflow."));
+        }
+
+        let Obj::Generator(state) = self.objects.get_mut(generator) else {
+            panic!("Expected a generator.");
+        };
+        match state.state {
+            GeneratorState::Running => return Err(String::from("Generator is already running.")),
+            GeneratorState::Done => {
+                self.pop();
+                self.push(Value::Nil);
+                return Ok(());
+            }
+            GeneratorState::Suspended => state.state = GeneratorState::Running,
+        }
+
+        let window = mem::take(&mut state.stack);
+        let handlers = mem::take(&mut state.handlers);
+        let upvalues = mem::take(&mut state.upvalues);
+        let (closure, function, ip) = (state.closure, state.function, state.ip);
+
+        self.pop();
+        let slot_base = self.stack.len();
+        self.stack.extend(window);
+        self.retarget_upvalues(&upvalues, self.fiber, 0, slot_base);
+        self.open_upvalues.extend(upvalues);
+        self.frames.push(CallFrame {
+            closure,
+            function,
+            ip,
+            slot_base,
+            iterator: false,
+            generator: Some(generator),
+        });
+
+        let frame_count = self.frames.len();
+        self.handlers
+            .extend(handlers.into_iter().map(|(catch_ip, offset)| Handler {
+                frame_count,
+                stack_len: slot_base + offset,
+                catch_ip,
+            }));
+        Ok(())
+    }
+
+    /// Also forgets modules whose top-level code failed, so they can be
+    /// imported again.
+    fn abandon_frames(&mut self, frames: Vec<CallFrame>) {
+        for frame in &frames {
+            let function = self.function(frame.function);
+            let module = function.module;
+            if function.is_script()
+                && !self.modules[module].loaded
+                && let Some(path) = &self.modules[module].path
+            {
+                self.module_paths.remove(path);
+            }
+        }
+        self.finish_generators(frames);
+    }
+
+    fn finish_generators(&mut self, frames: Vec<CallFrame>) {
+        for frame in frames {
+            if let Some(generator) = frame.generator
+                && let Obj::Generator(state) = self.objects.get_mut(generator)
+            {
+                state.state = GeneratorState::Done;
+            }
+        }
+    }
+
+    fn call_native(&mut self, native: Native, arg_count: usize) -> Result<(), String> {
+        let max_arity = native.arity + native.optional;
+        if arg_count < native.arity || arg_count > max_arity {
+            return Err(if native.optional == 0 {
+                format!("Expected {} arguments but got {arg_count}.", native.arity)
+            } else {
+                format!(
+                    "Expected {} to {max_arity} arguments but got {arg_count}.",
+                    native.arity
+                )
+            });
+        }
+
+        let args_start = self.stack.len() - arg_count;
+        let mut args = self.stack[args_start..].to_vec();
+        args.resize(max_arity, Value::Nil);
+        let result = (native.function)(self, &args)?;
+        self.stack.truncate(args_start - 1);
+        self.push(result);
+
+        // The result is a placeholder for what the other fiber hands back.
+        match self.fiber_switch.take() {
+            Some(FiberSwitch::Resume(fiber, value)) => {
+                let current = self.fiber;
+                let Obj::Fiber(target) = self.objects.get_mut(fiber) else {
+                    panic!("Expected a fiber.");
+                };
+                let started = target.state == FiberState::Suspended;
+                target.state = FiberState::Running;
+                target.caller = Some(current);
+                self.switch_fiber(fiber);
+                if started {
+                    *self.stack.last_mut().expect("Resumed with an empty stack.") = value;
+                }
+            }
+            Some(FiberSwitch::Suspend(value)) => {
+                let caller = self.fiber_caller(FiberState::Suspended);
+                self.switch_fiber(caller);
+                *self.stack.last_mut().expect("Resumed with an empty stack.") = value;
             }
+            None => {}
+        }
+        Ok(())
+    }
+
+    pub(crate) fn resume_fiber(&mut self, fiber: Value, value: Value) -> Result<(), String> {
+        let Some(Obj::Fiber(state)) = self.object(fiber) else {
+            return Err(String::from("Can only resume a fiber."));
+        };
+
+        match state.state {
+            FiberState::New | FiberState::Suspended => {
+                self.fiber_switch = Some(FiberSwitch::Resume(fiber.as_obj(), value));
+                Ok(())
+            }
+            FiberState::Running => Err(String::from("Fiber is already running.")),
+            FiberState::Done => Err(String::from("Cannot resume a finished fiber.")),
+        }
+    }
+
+    pub(crate) fn suspend_fiber(&mut self, value: Value) -> Result<(), String> {
+        if self.fiber == self.main_fiber {
+            return Err(String::from("Cannot suspend the main fiber."));
+        }
+
+        self.fiber_switch = Some(FiberSwitch::Suspend(value));
+        Ok(())
+    }
+
+    fn finish_fiber(&mut self, result: Value) {
+        let caller = self.fiber_caller(FiberState::Done);
+        self.switch_fiber(caller);
+        *self.stack.last_mut().expect("Resumed with an empty stack.") = result;
+    }
+
+    fn fiber_caller(&mut self, state: FiberState) -> usize {
+        let Obj::Fiber(fiber) = self.objects.get_mut(self.fiber) else {
+            panic!("Expected a fiber.");
+        };
+        fiber.state = state;
+        fiber.caller.take().expect("Fiber without a caller.")
+    }
+
+    fn switch_fiber(&mut self, target: usize) {
+        let Obj::Fiber(current) = self.objects.get_mut(self.fiber) else {
+            panic!("Expected a fiber.");
+        };
+        current.frames = mem::take(&mut self.frames);
+        current.stack = mem::take(&mut self.stack);
+        current.open_upvalues = mem::take(&mut self.open_upvalues);
+        current.handlers = mem::take(&mut self.handlers);
+
+        let Obj::Fiber(next) = self.objects.get_mut(target) else {
+            panic!("Expected a fiber.");
+        };
+        self.frames = mem::take(&mut next.frames);
+        self.stack = mem::take(&mut next.stack);
+        self.open_upvalues = mem::take(&mut next.open_upvalues);
+        self.handlers = mem::take(&mut next.handlers);
+        self.fiber = target;
+    }
+
+    fn owner_stack(&self, owner: usize) -> &Vec<Value> {
+        if owner == self.fiber {
+            return &self.stack;
+        }
+
+        match self.objects.get(owner) {
+            Obj::Fiber(fiber) => &fiber.stack,
+            Obj::Generator(generator) => &generator.stack,
+            obj => panic!("Expected a fiber or generator but found {obj}."),
+        }
+    }
+
+    fn owner_stack_mut(&mut self, owner: usize) -> &mut Vec<Value> {
+        if owner == self.fiber {
+            return &mut self.stack;
+        }
+
+        match self.objects.get_mut(owner) {
+            Obj::Fiber(fiber) => &mut fiber.stack,
+            Obj::Generator(generator) => &mut generator.stack,
+            obj => panic!("Expected a fiber or generator but found {obj}."),
+        }
+    }
+
+    fn unwind(&mut self, error: Value) -> bool {
+        let Some(handler) = self.handlers.pop() else {
+            return false;
+        };
+
+        let abandoned = self.frames.split_off(handler.frame_count);
+        self.abandon_frames(abandoned);
+        self.close_upvalues(handler.stack_len);
+        self.stack.truncate(handler.stack_len);
+        self.push(error);
+        self.frame_mut().ip = handler.catch_ip;
+        true
+    }
+
+    fn iterator_next(&mut self, iterator: Value) -> Option<Value> {
+        let Some(Obj::Iterator(state)) = self.object(iterator) else {
+            panic!("Expected an iterator.");
+        };
+        let IteratorState { target, position } = *state;
+
+        let (next, step) = match self.object(target) {
+            Some(Obj::List(list)) => (list.get(position).copied(), 1),
+            Some(Obj::Map(map)) => (map.entries().get(position).map(|(key, _)| *key), 1),
+            Some(Obj::Range(range)) => (range.get(position).map(Value::Number), 1),
+            Some(Obj::Str(string)) => {
+                let c = string[position..].chars().next()?;
+                let value = self.alloc(Obj::Str(c.to_string()));
+                (Some(value), c.len_utf8())
+            }
+            _ => panic!("Iterator over a value that cannot be iterated."),
+        };
+
+        if let Some(Obj::Iterator(state)) = self.object_mut(iterator) {
+            state.position += step;
+        }
+        next
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> usize {
+        for &upvalue in &self.open_upvalues {
+            if let Obj::Upvalue(Upvalue::Open { slot: open, .. }) = self.objects.get(upvalue)
+                && *open == slot
+            {
+                return upvalue;
+            }
+        }
+
+        let owner = self.fiber;
+        let upvalue = self
+            .alloc(Obj::Upvalue(Upvalue::Open { owner, slot }))
+            .as_obj();
+        self.open_upvalues.push(upvalue);
+        upvalue
+    }
+
+    fn close_upvalues(&mut self, from_slot: usize) {
+        let stack = &self.stack;
+        let objects = &mut self.objects;
+        self.open_upvalues
+            .retain(|&upvalue| match objects.get_mut(upvalue) {
+                Obj::Upvalue(upvalue) => match *upvalue {
+                    Upvalue::Open { slot, .. } if slot >= from_slot => {
+                        *upvalue = Upvalue::Closed(stack[slot]);
+                        false
+                    }
+                    _ => true,
+                },
+                obj => panic!("Expected an upvalue but found {obj}."),
+            });
+    }
+
+    fn take_upvalues(&mut self, from_slot: usize) -> Vec<usize> {
+        let objects = &self.objects;
+        let (taken, kept) =
+            self.open_upvalues
+                .iter()
+                .partition(|&&upvalue| match objects.get(upvalue) {
+                    Obj::Upvalue(Upvalue::Open { slot, .. }) => *slot >= from_slot,
+                    _ => false,
+                });
+        self.open_upvalues = kept;
+        taken
+    }
+
+    fn retarget_upvalues(
+        &mut self,
+        upvalues: &[usize],
+        owner: usize,
+        from_slot: usize,
+        to_slot: usize,
+    ) {
+        for &upvalue in upvalues {
+            if let Obj::Upvalue(Upvalue::Open {
+                owner: current,
+                slot,
+            }) = self.objects.get_mut(upvalue)
+            {
+                *current = owner;
+                *slot = *slot - from_slot + to_slot;
+            }
+        }
+    }
+
+    fn closure_upvalue(&self, closure: usize, index: usize) -> usize {
+        match self.objects.get(closure) {
+            Obj::Closure(closure) => closure.upvalues[index],
+            obj => panic!("Expected a closure but found {obj}."),
+        }
+    }
+
+    fn function(&self, function: usize) -> &Function {
+        match self.objects.get(function) {
+            Obj::Function(function) => function,
+            obj => panic!("Expected a function but found {obj}."),
+        }
+    }
+
+    fn chunk(&self, function: usize) -> &Chunk {
+        &self.function(function).chunk
+    }
+
+    fn frame(&self) -> CallFrame {
+        *self.frames.last().expect("No call frame.")
+    }
+
+    fn frame_mut(&mut self) -> &mut CallFrame {
+        self.frames.last_mut().expect("No call frame.")
+    }
+
+    fn read_constant(&self, index: usize) -> Value {
+        *self.chunk(self.frame().function).read_constant(index)
+    }
+
+    fn values_equal(&self, first: Value, second: Value) -> bool {
+        match (first, second) {
+            (Value::Bool(first), Value::Bool(second)) => first == second,
+            (Value::Number(first), Value::Number(second)) => first == second,
+            (Value::Nil, Value::Nil) => true,
+            (Value::Obj(first), Value::Obj(second)) => {
+                self.objects.get(first) == self.objects.get(second)
+            }
+            _ => false,
+        }
+    }
+
+    fn contains(&self, container: Value, item: Value) -> Result<bool, String> {
+        match self.object(container) {
+            Some(Obj::List(list)) => {
+                Ok(list.iter().any(|element| self.values_equal(*element, item)))
+            }
+            Some(Obj::Map(map)) => Ok(map.contains(&self.map_key(item)?)),
+            Some(Obj::Str(string)) => match self.object(item) {
+                Some(Obj::Str(substring)) => Ok(string.contains(substring.as_str())),
+                _ => Err(String::from("Can only search for a string in a string.")),
+            },
+            Some(Obj::Range(range)) => Ok(matches!(item, Value::Number(n) if range.contains(n))),
+            _ => Err(String::from(
+                "Can only check membership in a list, map, string or range.",
+            )),
+        }
+    }
+
+    fn slice(&mut self, target: Value, range: Range) -> Result<Value, String> {
+        let sliced = match self.object(target) {
+            Some(Obj::List(list)) => {
+                let positions = slice_positions(&range, list.len())?;
+                Obj::List(positions.into_iter().map(|i| list[i]).collect())
+            }
+            Some(Obj::Str(string)) => {
+                let chars: Vec<char> = string.chars().collect();
+                let positions = slice_positions(&range, chars.len())?;
+                Obj::Str(positions.into_iter().map(|i| chars[i]).collect())
+            }
+            _ => return Err(String::from("Only lists and strings can be sliced.")),
+        };
+
+        Ok(self.alloc(sliced))
+    }
+
+    fn get_index(&self, target: Value, index: Value) -> Result<Value, String> {
+        match self.object(target) {
+            Some(Obj::List(list)) => {
+                let position = list_index(index, list.len())?;
+                Ok(list[position])
+            }
+            Some(Obj::Map(map)) => Ok(map.get(&self.map_key(index)?).unwrap_or(Value::Nil)),
+            _ => Err(String::from("Only lists and maps can be indexed.")),
+        }
+    }
+
+    fn set_index(&mut self, target: Value, index: Value, value: Value) -> Result<(), String> {
+        let key = match self.object(target) {
+            Some(Obj::Map(_)) => Some(self.map_key(index)?),
+            _ => None,
+        };
+
+        match (self.object_mut(target), key) {
+            (Some(Obj::List(list)), _) => {
+                let position = list_index(index, list.len())?;
+                list[position] = value;
+                Ok(())
+            }
+            (Some(Obj::Map(map)), Some(key)) => {
+                map.insert(key, index, value);
+                Ok(())
+            }
+            _ => Err(String::from("Only lists and maps can be indexed.")),
         }
     }
 
     fn add(&mut self) -> Result<(), String> {
         match (self.peek(0), self.peek(1)) {
+            // An error joins strings as its message.
             (Value::Obj(b), Value::Obj(a)) => match (self.objects.get(*a), self.objects.get(*b)) {
-                (Obj::Str(a_str), Obj::Str(b_str)) => {
+                (Obj::Str(a_str) | Obj::Error(a_str), Obj::Str(b_str) | Obj::Error(b_str)) => {
                     let string = Obj::Str(format!("{}{}", a_str, b_str));
                     self.objects.push(string);
                     let value = Value::Obj(self.objects.len() - 1);
@@ -231,6 +1322,19 @@ impl Vm {
         Ok(())
     }
 
+    fn integer_op(&mut self, op: impl Fn(i64, i64) -> Result<i64, String>) -> Result<(), String> {
+        let (Some(right), Some(left)) = (as_integer(*self.peek(0)), as_integer(*self.peek(1)))
+        else {
+            return Err(String::from("Operands must both be integers."));
+        };
+
+        let result = op(left, right)?;
+        self.pop();
+        self.pop();
+        self.push(Value::Number(result as f64));
+        Ok(())
+    }
+
     fn pop(&mut self) -> Value {
         self.stack
             .pop()
@@ -245,12 +1349,14 @@ impl Vm {
     }
 
     fn local_at(&self, index: usize) -> &Value {
-        self.stack.get(index).expect("Local index is out-of-bounds")
+        let slot = self.frame().slot_base + index;
+        self.stack.get(slot).expect("Local index is out-of-bounds")
     }
 
     fn local_at_mut(&mut self, index: usize) -> &mut Value {
+        let slot = self.frame().slot_base + index;
         self.stack
-            .get_mut(index)
+            .get_mut(slot)
             .expect("Local index is out-of-bounds")
     }
 
@@ -258,19 +1364,30 @@ impl Vm {
         self.stack.push(value);
     }
 
-    fn stack_trace(&self) {
-        if self.stack.is_empty() {
-            print!("[ ]");
-        }
-
-        for (stack_index, value) in self.stack.iter().enumerate() {
-            match value {
-                Value::Obj(index) => print!("{stack_index}:[ {} ] ", self.objects.get(*index)),
-                _ => print!("{stack_index}:[ {value} ] "),
-            }
-        }
+    fn trace_instruction(&mut self, ip: usize, op: &Op) {
+        let stack = self
+            .stack
+            .iter()
+            .map(|value| {
+                format!(
+                    "\"{}\"",
+                    native::json::escape(&format_value(value, &self.objects))
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(",");
+        let operand = match op.operand() {
+            Some(operand) => operand.to_string(),
+            None => String::from("null"),
+        };
 
-        println!();
+        writeln!(
+            self.config.trace_output,
+            "{{\"ip\":{ip},\"line\":{},\"op\":\"{}\",\"operand\":{operand},\"stack\":[{stack}]}}",
+            self.chunk(self.frame().function).get_line(ip),
+            op.name(),
+        )
+        .expect("Error writing execution trace.");
     }
 
     fn stack_top(&self) -> usize {
@@ -278,20 +1395,126 @@ impl Vm {
     }
 
     fn reset_stack(&mut self) {
-        self.ip = 0;
+        let abandoned = mem::take(&mut self.frames);
+        self.abandon_frames(abandoned);
         self.stack.clear();
+        self.open_upvalues.clear();
+        self.handlers.clear();
+    }
+
+    fn write_output(&mut self, text: &str) {
+        write!(self.config.output, "{text}").expect("Error writing program output.");
+        self.config
+            .output
+            .flush()
+            .expect("Error flushing program output.");
     }
 
-    fn runtime_error(&mut self, message: &str, chunk: &Chunk) -> Interpret {
-        eprintln!("{message}");
-        let ip = self.ip - 1;
-        let line = chunk.get_line(ip);
-        eprintln!("[line {line}] in script.");
+    fn diagnostic(&mut self, message: &str) {
+        writeln!(self.config.diagnostics, "{message}").expect("Error writing diagnostics.");
+    }
+
+    fn runtime_error(&mut self, message: &str, mut trace: Vec<String>) -> Interpret {
+        self.diagnostic(message);
+        trace.extend(self.stack_trace());
+        for line in trace {
+            self.diagnostic(&line);
+        }
         self.reset_stack();
         Interpret::RuntimeError
     }
+
+    fn stack_trace(&self) -> Vec<String> {
+        self.frames
+            .iter()
+            .rev()
+            .map(|frame| {
+                let function = self.function(frame.function);
+                let line = function.chunk.get_line(frame.ip.saturating_sub(1));
+                if function.module != MAIN_MODULE && function.is_script() {
+                    format!("[line {line}] in {}.", self.modules[function.module].name)
+                } else if function.is_script() {
+                    format!("[line {line}] in script.")
+                } else {
+                    format!("[line {line}] in {}().", function.name)
+                }
+            })
+            .collect()
+    }
+}
+
+fn as_integer(value: Value) -> Option<i64> {
+    match value {
+        Value::Number(n) if n.fract() == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 => {
+            Some(n as i64)
+        }
+        _ => None,
+    }
+}
+
+fn shift_amount(amount: i64) -> Result<i64, String> {
+    if (0..64).contains(&amount) {
+        Ok(amount)
+    } else {
+        Err(format!("Shift amount {amount} must be between 0 and 63."))
+    }
+}
+
+fn list_index(index: Value, len: usize) -> Result<usize, String> {
+    let index = match index {
+        Value::Number(n) if n.fract() == 0.0 => n,
+        _ => return Err(String::from("List index must be an integer.")),
+    };
+
+    if index < 0.0 {
+        return Err(format!("List index {index} cannot be negative."));
+    }
+
+    if index >= len as f64 {
+        return Err(format!(
+            "List index {index} is out of bounds for list of length {len}."
+        ));
+    }
+
+    Ok(index as usize)
+}
+
+fn disassemble_function(
+    function: &Function,
+    objects: &Arena<Obj>,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    function
+        .chunk
+        .disassemble(&function.to_string(), objects, out)?;
+    for constant in function.chunk.constants() {
+        if let Value::Obj(index) = constant
+            && let Obj::Function(nested) = objects.get(*index)
+        {
+            disassemble_function(nested, objects, out)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn slice_positions(range: &Range, len: usize) -> Result<Vec<usize>, String> {
+    (0..range.len())
+        .map(|i| match range.get(i) {
+            Some(n) if n.fract() != 0.0 => Err(String::from("Slice bounds must be integers.")),
+            Some(n) if n >= 0.0 && n < len as f64 => Ok(n as usize),
+            _ => Err(format!("Slice {range} is out of bounds for length {len}.")),
+        })
+        .collect()
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Interpret {
     Ok,
     CompileError,
@@ -0,0 +1,61 @@
+//! Runs scripts against a `ManualClock` to check that the `time` module
+//! reads the clock the VM was configured with.
+
+use std::{env, fs, process};
+
+use blox2::{
+    clock::{Clock, ManualClock},
+    config::{SharedBuffer, VmConfig},
+    vm::{Interpret, Vm},
+};
+
+fn run(name: &str, source: &str, clock: ManualClock) -> String {
+    let script = env::temp_dir().join(format!("blox2-clock-{name}-{}.lox", process::id()));
+    fs::write(&script, source).expect("Failed to write script");
+
+    let output = SharedBuffer::new();
+    let config = VmConfig::new().output(output.clone()).clock(clock);
+    let status = Vm::with_config(config).run_file(script.to_str().unwrap(), &[]);
+    _ = fs::remove_file(&script);
+
+    assert_eq!(status, Interpret::Ok);
+    output.contents()
+}
+
+#[test]
+fn reads_injected_time() {
+    let clock = ManualClock::new(1_700_000_000.0);
+    let output = run("now", "print time.format(time.now());", clock);
+    assert_eq!(output, "2023-11-14T22:13:20Z\n");
+}
+
+#[test]
+fn sleep_advances_fake_time() {
+    let clock = ManualClock::new(0.0);
+    let source = r#"
+        var start = time.monotonic();
+        time.sleep(3600);
+        print time.monotonic() - start;
+        print time.format(time.now());
+    "#;
+
+    let output = run("sleep", source, clock.clone());
+    assert_eq!(output, "3600\n1970-01-01T01:00:00Z\n");
+    assert_eq!(clock.now(), 3600.0);
+}
+
+#[test]
+fn wall_clock_can_jump_without_moving_monotonic() {
+    let clock = ManualClock::new(100.0);
+    clock.set(50.0);
+    let output = run("set", "print time.now(); print time.monotonic();", clock);
+    assert_eq!(output, "50\n0\n");
+}
+
+#[test]
+fn default_seed_comes_from_clock() {
+    let source = "print random.float();";
+    let first = run("seed-a", source, ManualClock::new(42.0));
+    let second = run("seed-b", source, ManualClock::new(42.0));
+    assert_eq!(first, second);
+}
@@ -0,0 +1,109 @@
+//! Checks of the `io` module that need more from the host than the script
+//! runner gives: a scratch directory, lines of input and a sandboxed VM.
+
+use std::{env, fs, io::Cursor, path::PathBuf, process};
+
+use blox2::{
+    config::{SharedBuffer, VmConfig},
+    vm::{Interpret, Vm},
+};
+
+/// A fresh directory for one test, removed when dropped.
+struct Scratch(PathBuf);
+
+impl Scratch {
+    fn new(name: &str) -> Self {
+        let dir = env::temp_dir().join(format!("blox2-io-{name}-{}", process::id()));
+        fs::create_dir_all(&dir).expect("Failed to create scratch directory");
+        Self(dir)
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().into_owned()
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Runs `source` from a file in `scratch`, returning the status with what
+/// it printed and reported.
+fn run(scratch: &Scratch, source: &str, config: VmConfig) -> (Interpret, String, String) {
+    let script = scratch.path("script.lox");
+    fs::write(&script, source).expect("Failed to write script");
+
+    let output = SharedBuffer::new();
+    let diagnostics = SharedBuffer::new();
+    let mut vm = Vm::with_config(
+        config
+            .output(output.clone())
+            .diagnostics(diagnostics.clone()),
+    );
+    let status = vm.run_file(&script, &[]);
+    (status, output.contents(), diagnostics.contents())
+}
+
+#[test]
+fn write_and_append() {
+    let scratch = Scratch::new("write");
+    let file = scratch.path("out.txt");
+    let source = format!(
+        r#"
+        var path = "{file}";
+        io.writeFile(path, "one\n");
+        io.appendFile(path, "two\n");
+        print io.readFile(path);
+        io.writeFile(path, "replaced");
+        print io.readFile(path);
+        "#
+    );
+
+    let (status, output, _) = run(&scratch, &source, VmConfig::new().input(Cursor::new("")));
+    assert_eq!(status, Interpret::Ok);
+    assert_eq!(output, "one\ntwo\n\nreplaced\n");
+    assert_eq!(fs::read_to_string(file).unwrap(), "replaced");
+}
+
+#[test]
+fn append_creates_file() {
+    let scratch = Scratch::new("append");
+    let file = scratch.path("new.txt");
+    let source = format!(r#"io.appendFile("{file}", "created");"#);
+
+    let (status, _, _) = run(&scratch, &source, VmConfig::new());
+    assert_eq!(status, Interpret::Ok);
+    assert_eq!(fs::read_to_string(file).unwrap(), "created");
+}
+
+#[test]
+fn read_lines_from_input() {
+    let scratch = Scratch::new("input");
+    let source = r#"
+        var line = io.readLine();
+        while (line != nil) {
+          print "[" + line + "]";
+          line = io.readLine();
+        }
+    "#;
+
+    let config = VmConfig::new().input(Cursor::new("first\r\nsecond\nlast"));
+    let (status, output, _) = run(&scratch, source, config);
+    assert_eq!(status, Interpret::Ok);
+    assert_eq!(output, "[first]\n[second]\n[last]\n");
+}
+
+#[test]
+fn sandboxed_vm_has_no_io() {
+    let scratch = Scratch::new("sandbox");
+    let file = scratch.path("secret.txt");
+    fs::write(&file, "secret").unwrap();
+    let source = format!(r#"print io.readFile("{file}");"#);
+
+    let (status, output, diagnostics) = run(&scratch, &source, VmConfig::new().io(false));
+    assert_eq!(status, Interpret::RuntimeError);
+    assert_eq!(output, "");
+    assert!(diagnostics.starts_with("Undefined variable 'io'"));
+}
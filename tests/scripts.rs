@@ -0,0 +1,183 @@
+//! Runs every `.lox` file under `tests/scripts` and checks it against the
+//! expectations written in its comments:
+//!
+//! - `// expect: <text>` - the next line printed by the script.
+//! - `// expect compile error: <text>` - a compile error reported on this line
+//!   whose message (after the `[line ...]` prefix) is `<text>`.
+//! - `// expect runtime error: <text>` - the script aborts with `<text>` on
+//!   this line. Only the innermost frame of the stack trace is checked.
+//!
+//! Scripts read from an empty input, so `io.readLine()` gives nil.
+//!
+//! Files under a `modules` directory are only there to be imported by other
+//! scripts and are not run on their own.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use blox2::{
+    config::{SharedBuffer, VmConfig},
+    vm::{Interpret, Vm},
+};
+
+const SCRIPTS_DIR: &str = "tests/scripts";
+const EXPECT: &str = "// expect: ";
+const EXPECT_COMPILE_ERROR: &str = "// expect compile error: ";
+const EXPECT_RUNTIME_ERROR: &str = "// expect runtime error: ";
+const MODULES_DIR: &str = "modules";
+
+#[derive(Debug, Default)]
+struct Expectations {
+    output: Vec<String>,
+    compile_errors: Vec<String>,
+    runtime_error: Option<String>,
+}
+
+impl Expectations {
+    fn parse(source: &str) -> Self {
+        let mut expectations = Self::default();
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            if let Some(text) = annotation(line, EXPECT) {
+                expectations.output.push(text.to_string());
+            } else if let Some(text) = annotation(line, EXPECT_COMPILE_ERROR) {
+                expectations
+                    .compile_errors
+                    .push(format!("[line {line_number}] {text}"));
+            } else if let Some(text) = annotation(line, EXPECT_RUNTIME_ERROR) {
+                expectations.runtime_error = Some(format!("{text}\n[line {line_number}] in "));
+            }
+        }
+
+        expectations
+    }
+
+    fn status(&self) -> Interpret {
+        if !self.compile_errors.is_empty() {
+            Interpret::CompileError
+        } else if self.runtime_error.is_some() {
+            Interpret::RuntimeError
+        } else {
+            Interpret::Ok
+        }
+    }
+}
+
+fn annotation<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    line.find(marker)
+        .map(|start| line[start + marker.len()..].trim_end())
+}
+
+/// Drops the column and length details from a compile error so expectations
+/// only need to name the line.
+fn strip_columns(error: &str) -> String {
+    match (error.find(" col "), error.find("] ")) {
+        (Some(col), Some(end)) if col < end => format!("{}{}", &error[..col], &error[end..]),
+        _ => error.to_string(),
+    }
+}
+
+fn collect_scripts(dir: &Path, scripts: &mut Vec<PathBuf>) {
+    let entries = fs::read_dir(dir).unwrap_or_else(|e| panic!("Failed to read {dir:?}: {e}"));
+    for entry in entries {
+        let path = entry.expect("Failed to read directory entry").path();
+        if path.is_dir() && path.file_name().is_some_and(|name| name == MODULES_DIR) {
+            continue;
+        } else if path.is_dir() {
+            collect_scripts(&path, scripts);
+        } else if path.extension().is_some_and(|extension| extension == "lox") {
+            scripts.push(path);
+        }
+    }
+}
+
+fn run_script(path: &Path) -> Result<(), String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("failed to read: {e}"))?;
+    let expectations = Expectations::parse(&source);
+
+    let output = SharedBuffer::new();
+    let diagnostics = SharedBuffer::new();
+    let config = VmConfig::new()
+        .output(output.clone())
+        .diagnostics(diagnostics.clone())
+        .input(io::empty());
+    let mut vm = Vm::with_config(config);
+    let status = vm.run_file(path.to_str().expect("Script path is not UTF-8"), &[]);
+
+    let mut failures = Vec::new();
+    if status != expectations.status() {
+        failures.push(format!(
+            "expected status {:?} but got {status:?}",
+            expectations.status()
+        ));
+    }
+
+    let actual_output: Vec<String> = output.contents().lines().map(String::from).collect();
+    if actual_output != expectations.output {
+        failures.push(format!(
+            "expected output {:?} but got {actual_output:?}",
+            expectations.output
+        ));
+    }
+
+    let diagnostics = diagnostics.contents();
+    match status {
+        Interpret::CompileError => {
+            let actual: Vec<String> = diagnostics.lines().map(strip_columns).collect();
+            if actual != expectations.compile_errors {
+                failures.push(format!(
+                    "expected compile errors {:?} but got {actual:?}",
+                    expectations.compile_errors
+                ));
+            }
+        }
+        Interpret::RuntimeError => {
+            let actual = diagnostics.trim_end();
+            let expected = expectations.runtime_error.as_deref();
+            if !expected.is_some_and(|expected| actual.starts_with(expected)) {
+                failures.push(format!(
+                    "expected runtime error {:?} but got {actual:?}",
+                    expectations.runtime_error
+                ));
+            }
+        }
+        Interpret::Ok => {
+            if !diagnostics.is_empty() {
+                failures.push(format!("unexpected diagnostics {diagnostics:?}"));
+            }
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(failures.join("\n    "))
+    }
+}
+
+#[test]
+fn scripts() {
+    let mut scripts = Vec::new();
+    collect_scripts(Path::new(SCRIPTS_DIR), &mut scripts);
+    scripts.sort();
+    assert!(!scripts.is_empty(), "No scripts found under {SCRIPTS_DIR}");
+
+    let failures: Vec<String> = scripts
+        .iter()
+        .filter_map(|path| {
+            run_script(path)
+                .err()
+                .map(|failure| format!("{}:\n    {failure}", path.display()))
+        })
+        .collect();
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} scripts failed:\n{}",
+        failures.len(),
+        scripts.len(),
+        failures.join("\n")
+    );
+}